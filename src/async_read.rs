@@ -0,0 +1,103 @@
+use std::io::{self, Stdout};
+
+use crossterm::event::EventStream;
+use futures_util::StreamExt;
+
+use crate::engine::{print_crlf, Engine, LineState, Signal};
+use crate::error::Result;
+use crate::terminal::RawModeGuard;
+
+/// A `read_line_async` in progress, which cleans up the screen and gives up on
+/// the line if it is dropped before the line is done
+struct AsyncReadLine<'a> {
+    _raw_mode: RawModeGuard,
+    engine: &'a mut Engine,
+    stdout: &'a mut Stdout,
+    state: LineState,
+    done: bool,
+}
+
+impl Drop for AsyncReadLine<'_> {
+    fn drop(&mut self) {
+        if !self.done {
+            // Errors can't go anywhere from here, the screen is just left as it is
+            self.state.pending.clear();
+            let _ = self.engine.repaint(self.stdout, &self.state);
+            let _ = print_crlf(self.stdout);
+            self.engine.cancel_line(&mut self.state);
+        }
+        let _ = self.state.set_block_cursor(self.stdout, false);
+    }
+}
+
+impl AsyncReadLine<'_> {
+    async fn run(&mut self) -> Result<Signal> {
+        let mut events = EventStream::new();
+        self.engine.repaint(self.stdout, &self.state)?;
+
+        loop {
+            let timeout = self.state.wake_up_timeout(None);
+            let signal = match tokio::time::timeout(timeout, events.next()).await {
+                Ok(Some(event)) => {
+                    self.engine
+                        .handle_event(self.stdout, &mut self.state, event?)?
+                }
+                Ok(None) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "terminal event stream ended",
+                    )
+                    .into())
+                }
+                Err(_) => self.engine.handle_wake_up(self.stdout, &mut self.state)?,
+            };
+            if let Some(signal) = signal {
+                return Ok(signal);
+            }
+        }
+    }
+}
+
+impl Engine {
+    /// Like `read_line`, but waits for key presses without blocking the thread.
+    /// Needs a tokio runtime with the time driver enabled.
+    ///
+    /// Dropping the future cancels reading: the prompt is drawn once more without
+    /// any pending chord, the cursor moves to the next line, and the line is
+    /// given up on like a `read_line_until` that timed out.
+    pub async fn read_line_async(&mut self, stdout: &mut Stdout) -> Result<Signal> {
+        let raw_mode = RawModeGuard::new()?;
+        let state = self.start_line()?;
+        let mut read_line = AsyncReadLine {
+            _raw_mode: raw_mode,
+            engine: self,
+            stdout,
+            state,
+            done: false,
+        };
+
+        let signal = read_line.run().await;
+        read_line.done = true;
+
+        signal
+    }
+}
+
+#[test]
+fn cancel_test() {
+    use crate::engine::EditCommand;
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    let mut engine = Engine::new();
+    let mut state = LineState::at_column(0);
+    engine.run_edit_commands(&[EditCommand::InsertChar('a'), EditCommand::MoveRight]);
+    state
+        .pending
+        .push(KeyEvent::new(KeyCode::Char('x'), KeyModifiers::CONTROL));
+
+    engine.cancel_line(&mut state);
+    assert!(state.pending.is_empty());
+    // Nothing is left of the line to move over
+    engine.run_edit_commands(&[EditCommand::MoveToEnd]);
+    assert_eq!(engine.get_insertion_point(), 0);
+}
@@ -0,0 +1,86 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use crossterm::style::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::keybindings::Keybindings;
+
+/// Settings an `Engine` can be started with, usually read from a TOML file
+/// like the `red_line.toml` at the root of this repository.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// How many lines the history keeps
+    pub history_size: usize,
+    /// How long to wait for the rest of a chord, in milliseconds
+    pub chord_timeout: u64,
+    pub prompt_color: Color,
+    /// Color of a pending chord shown in the prompt
+    pub chord_color: Color,
+    /// Bindings added on top of the default emacs bindings
+    pub keybindings: Keybindings,
+    /// Words expanded when followed by space or enter, like `gco = "git checkout"`
+    pub abbreviations: HashMap<String, String>,
+    /// Insert closing brackets and quotes along with the opening ones
+    pub auto_pair: bool,
+    /// Let Ctrl-Z suspend the process
+    pub suspend: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            history_size: 100,
+            chord_timeout: 500,
+            prompt_color: Color::Blue,
+            chord_color: Color::Yellow,
+            keybindings: Keybindings::new(),
+            abbreviations: HashMap::new(),
+            auto_pair: false,
+            suspend: true,
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Config> {
+        let in_file =
+            |err: &dyn std::fmt::Display| Error::Config(format!("{}: {}", path.display(), err));
+        let contents = fs::read_to_string(path).map_err(|err| in_file(&err))?;
+        toml::from_str(&contents).map_err(|err| in_file(&err))
+    }
+
+    pub fn from_toml(contents: &str) -> Result<Config> {
+        toml::from_str(contents).map_err(|err| Error::Config(err.to_string()))
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string(self).map_err(|err| Error::Config(err.to_string()))
+    }
+}
+
+#[test]
+fn shared_config_test() {
+    let config = Config::from_toml(include_str!("../red_line.toml")).unwrap();
+    assert_eq!(config.history_size, 500);
+    assert_eq!(config.prompt_color, Color::Green);
+    assert_eq!(config.keybindings.bindings().len(), 7);
+    assert_eq!(config.abbreviations["gco"], "git checkout");
+
+    let round_tripped = Config::from_toml(&config.to_toml().unwrap()).unwrap();
+    assert_eq!(round_tripped.chord_timeout, config.chord_timeout);
+    assert_eq!(round_tripped.keybindings.bindings().len(), 7);
+}
+
+#[test]
+fn config_error_test() {
+    let err = Config::from_toml("history_size = \"lots\"").err().unwrap();
+    assert!(matches!(err, Error::Config(_)));
+    assert!(err.is_recoverable());
+
+    let err = Config::load(Path::new("does/not/exist.toml"))
+        .err()
+        .unwrap();
+    assert!(err.to_string().starts_with("does/not/exist.toml: "));
+}
@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use regex::Regex;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Decides whether a character may be typed or pasted into the line
+pub type CharFilter = Arc<dyn Fn(char) -> bool + Send + Sync>;
+
+/// How the user is told that a key was refused
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InvalidInputFeedback {
+    /// Ring the terminal bell
+    Bell,
+    /// Briefly draw the prompt in red
+    Flash,
+}
+
+/// Limits on what `Engine::read_line_with_constraints` accepts, for
+/// form-like prompts such as a port number or a hex color.
+/// Input that breaks them is refused instead of being inserted.
+#[derive(Clone)]
+pub struct InputConstraints {
+    max_length: Option<usize>,
+    char_filter: Option<CharFilter>,
+    pattern: Option<Regex>,
+    feedback: InvalidInputFeedback,
+}
+
+impl Default for InputConstraints {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputConstraints {
+    /// No constraints at all, refusals ring the bell
+    pub fn new() -> InputConstraints {
+        InputConstraints {
+            max_length: None,
+            char_filter: None,
+            pattern: None,
+            feedback: InvalidInputFeedback::Bell,
+        }
+    }
+
+    /// Accept at most `max_length` graphemes
+    pub fn with_max_length(mut self, max_length: usize) -> Self {
+        self.max_length = Some(max_length);
+        self
+    }
+
+    /// Only accept characters `filter` returns true for, like `char::is_ascii_digit`
+    pub fn with_char_filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(char) -> bool + Send + Sync + 'static,
+    {
+        self.char_filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// Only submit lines `pattern` matches. Anchor it with `^` and `$` to
+    /// match the whole line.
+    pub fn with_pattern(mut self, pattern: &str) -> Result<Self, regex::Error> {
+        self.pattern = Some(Regex::new(pattern)?);
+        Ok(self)
+    }
+
+    pub fn with_feedback(mut self, feedback: InvalidInputFeedback) -> Self {
+        self.feedback = feedback;
+        self
+    }
+
+    pub fn feedback(&self) -> InvalidInputFeedback {
+        self.feedback
+    }
+
+    pub fn allows_char(&self, c: char) -> bool {
+        self.char_filter.as_ref().is_none_or(|filter| filter(c))
+    }
+
+    pub fn allows_length(&self, line: &str) -> bool {
+        self.max_length
+            .is_none_or(|max_length| line.graphemes(true).count() <= max_length)
+    }
+
+    /// Whether `line` may be submitted
+    pub fn accepts(&self, line: &str) -> bool {
+        self.allows_length(line)
+            && line.chars().all(|c| self.allows_char(c))
+            && self
+                .pattern
+                .as_ref()
+                .is_none_or(|pattern| pattern.is_match(line))
+    }
+}
@@ -1,4 +1,6 @@
 use crate::engine::print_message;
+use crate::error::Result;
+use crate::terminal::RawModeGuard;
 use crossterm::event::{poll, read, Event, KeyCode};
 use std::{io::Stdout, time::Duration};
 
@@ -7,7 +9,9 @@ use std::{io::Stdout, time::Duration};
 // even seeing the events. if you press a key and no events
 // are printed, it's a good chance your terminal is eating
 // those events.
-pub fn print_events(stdout: &mut Stdout) -> Result<(), crossterm::ErrorKind> {
+pub fn print_events(stdout: &mut Stdout) -> Result<()> {
+    let _raw_mode = RawModeGuard::new()?;
+
     loop {
         // Wait up to 5s for another event
         if poll(Duration::from_millis(5_000))? {
@@ -0,0 +1,207 @@
+/// Emoji by the names GitHub and Slack know them by, sorted by name so they
+/// can be looked up with a binary search. Values are whole grapheme clusters,
+/// variation selectors and zero width joiners included.
+const EMOJI: &[(&str, &str)] = &[
+    ("+1", "\u{1F44D}"),
+    ("-1", "\u{1F44E}"),
+    ("100", "\u{1F4AF}"),
+    ("alien", "\u{1F47D}"),
+    ("angry", "\u{1F620}"),
+    ("apple", "\u{1F34E}"),
+    ("arrow_down", "\u{2B07}\u{FE0F}"),
+    ("arrow_left", "\u{2B05}\u{FE0F}"),
+    ("arrow_right", "\u{27A1}\u{FE0F}"),
+    ("arrow_up", "\u{2B06}\u{FE0F}"),
+    ("astonished", "\u{1F632}"),
+    ("bee", "\u{1F41D}"),
+    ("beer", "\u{1F37A}"),
+    ("beers", "\u{1F37B}"),
+    ("bell", "\u{1F514}"),
+    ("black_heart", "\u{1F5A4}"),
+    ("blue_heart", "\u{1F499}"),
+    ("blush", "\u{1F60A}"),
+    ("books", "\u{1F4DA}"),
+    ("boom", "\u{1F4A5}"),
+    ("brain", "\u{1F9E0}"),
+    ("broken_heart", "\u{1F494}"),
+    ("bug", "\u{1F41B}"),
+    ("bulb", "\u{1F4A1}"),
+    ("cake", "\u{1F370}"),
+    ("calendar", "\u{1F4C6}"),
+    ("cat", "\u{1F431}"),
+    ("chart_with_downwards_trend", "\u{1F4C9}"),
+    ("chart_with_upwards_trend", "\u{1F4C8}"),
+    ("checkered_flag", "\u{1F3C1}"),
+    ("clap", "\u{1F44F}"),
+    ("cloud", "\u{2601}\u{FE0F}"),
+    ("clown_face", "\u{1F921}"),
+    ("coffee", "\u{2615}"),
+    ("computer", "\u{1F4BB}"),
+    ("confetti_ball", "\u{1F38A}"),
+    ("confounded", "\u{1F616}"),
+    ("confused", "\u{1F615}"),
+    ("construction", "\u{1F6A7}"),
+    ("crab", "\u{1F980}"),
+    ("crossed_fingers", "\u{1F91E}"),
+    ("cry", "\u{1F622}"),
+    ("de", "\u{1F1E9}\u{1F1EA}"),
+    ("disappointed", "\u{1F61E}"),
+    ("dizzy_face", "\u{1F635}"),
+    ("dog", "\u{1F436}"),
+    ("earth_americas", "\u{1F30E}"),
+    ("email", "\u{1F4E7}"),
+    ("exclamation", "\u{2757}"),
+    ("expressionless", "\u{1F611}"),
+    ("eyes", "\u{1F440}"),
+    ("facepalm", "\u{1F926}"),
+    ("fire", "\u{1F525}"),
+    ("floppy_disk", "\u{1F4BE}"),
+    ("flushed", "\u{1F633}"),
+    ("fr", "\u{1F1EB}\u{1F1F7}"),
+    ("gb", "\u{1F1EC}\u{1F1E7}"),
+    ("gear", "\u{2699}\u{FE0F}"),
+    ("ghost", "\u{1F47B}"),
+    ("gift", "\u{1F381}"),
+    ("green_heart", "\u{1F49A}"),
+    ("grimacing", "\u{1F62C}"),
+    ("grinning", "\u{1F600}"),
+    ("hammer", "\u{1F528}"),
+    ("hear_no_evil", "\u{1F649}"),
+    ("heart", "\u{2764}\u{FE0F}"),
+    ("heart_eyes", "\u{1F60D}"),
+    ("heavy_check_mark", "\u{2714}\u{FE0F}"),
+    ("hourglass", "\u{231B}"),
+    ("innocent", "\u{1F607}"),
+    ("joy", "\u{1F602}"),
+    ("jp", "\u{1F1EF}\u{1F1F5}"),
+    ("key", "\u{1F511}"),
+    ("keyboard", "\u{2328}\u{FE0F}"),
+    ("kissing_heart", "\u{1F618}"),
+    ("laughing", "\u{1F606}"),
+    ("link", "\u{1F517}"),
+    ("lock", "\u{1F512}"),
+    ("mag", "\u{1F50D}"),
+    ("man_technologist", "\u{1F468}\u{200D}\u{1F4BB}"),
+    ("mask", "\u{1F637}"),
+    ("memo", "\u{1F4DD}"),
+    ("muscle", "\u{1F4AA}"),
+    ("nauseated_face", "\u{1F922}"),
+    ("nerd_face", "\u{1F913}"),
+    ("neutral_face", "\u{1F610}"),
+    ("no_entry", "\u{26D4}"),
+    ("no_entry_sign", "\u{1F6AB}"),
+    ("no_mouth", "\u{1F636}"),
+    ("ok_hand", "\u{1F44C}"),
+    ("open_mouth", "\u{1F62E}"),
+    ("orange_heart", "\u{1F9E1}"),
+    ("package", "\u{1F4E6}"),
+    ("pencil2", "\u{270F}\u{FE0F}"),
+    ("penguin", "\u{1F427}"),
+    ("pensive", "\u{1F614}"),
+    ("phone", "\u{260E}\u{FE0F}"),
+    ("pirate_flag", "\u{1F3F4}\u{200D}\u{2620}\u{FE0F}"),
+    ("pizza", "\u{1F355}"),
+    ("point_down", "\u{1F447}"),
+    ("point_left", "\u{1F448}"),
+    ("point_right", "\u{1F449}"),
+    ("point_up", "\u{261D}\u{FE0F}"),
+    ("poop", "\u{1F4A9}"),
+    ("pray", "\u{1F64F}"),
+    ("purple_heart", "\u{1F49C}"),
+    ("question", "\u{2753}"),
+    ("rage", "\u{1F621}"),
+    ("rainbow", "\u{1F308}"),
+    ("rainbow_flag", "\u{1F3F3}\u{FE0F}\u{200D}\u{1F308}"),
+    ("raised_hands", "\u{1F64C}"),
+    ("recycle", "\u{267B}\u{FE0F}"),
+    ("relieved", "\u{1F60C}"),
+    ("robot", "\u{1F916}"),
+    ("rocket", "\u{1F680}"),
+    ("rofl", "\u{1F923}"),
+    ("roll_eyes", "\u{1F644}"),
+    ("rotating_light", "\u{1F6A8}"),
+    ("scream", "\u{1F631}"),
+    ("see_no_evil", "\u{1F648}"),
+    ("shrug", "\u{1F937}"),
+    ("skull", "\u{1F480}"),
+    ("sleeping", "\u{1F634}"),
+    ("sleepy", "\u{1F62A}"),
+    ("slightly_smiling_face", "\u{1F642}"),
+    ("smile", "\u{1F604}"),
+    ("smiley", "\u{1F603}"),
+    ("smiling_imp", "\u{1F608}"),
+    ("smirk", "\u{1F60F}"),
+    ("snake", "\u{1F40D}"),
+    ("sneezing_face", "\u{1F927}"),
+    ("snowflake", "\u{2744}\u{FE0F}"),
+    ("sob", "\u{1F62D}"),
+    ("sparkles", "\u{2728}"),
+    ("sparkling_heart", "\u{1F496}"),
+    ("speak_no_evil", "\u{1F64A}"),
+    ("star", "\u{2B50}"),
+    ("star2", "\u{1F31F}"),
+    ("stopwatch", "\u{23F1}\u{FE0F}"),
+    ("stuck_out_tongue", "\u{1F61B}"),
+    ("stuck_out_tongue_winking_eye", "\u{1F61C}"),
+    ("sunglasses", "\u{1F60E}"),
+    ("sunny", "\u{2600}\u{FE0F}"),
+    ("sweat", "\u{1F613}"),
+    ("sweat_smile", "\u{1F605}"),
+    ("tada", "\u{1F389}"),
+    ("thinking", "\u{1F914}"),
+    ("thumbsdown", "\u{1F44E}"),
+    ("thumbsup", "\u{1F44D}"),
+    ("tired_face", "\u{1F62B}"),
+    ("triangular_flag_on_post", "\u{1F6A9}"),
+    ("triumph", "\u{1F624}"),
+    ("trophy", "\u{1F3C6}"),
+    ("umbrella", "\u{2614}"),
+    ("unamused", "\u{1F612}"),
+    ("unicorn", "\u{1F984}"),
+    ("unlock", "\u{1F513}"),
+    ("upside_down_face", "\u{1F643}"),
+    ("us", "\u{1F1FA}\u{1F1F8}"),
+    ("v", "\u{270C}\u{FE0F}"),
+    ("warning", "\u{26A0}\u{FE0F}"),
+    ("wave", "\u{1F44B}"),
+    ("weary", "\u{1F629}"),
+    ("whale", "\u{1F433}"),
+    ("white_check_mark", "\u{2705}"),
+    ("wink", "\u{1F609}"),
+    ("woman_technologist", "\u{1F469}\u{200D}\u{1F4BB}"),
+    ("worried", "\u{1F61F}"),
+    ("wrench", "\u{1F527}"),
+    ("x", "\u{274C}"),
+    ("yellow_heart", "\u{1F49B}"),
+    ("yum", "\u{1F60B}"),
+    ("zap", "\u{26A1}"),
+];
+
+/// The emoji called `name`, like `"smile"` for `:smile:`
+pub(crate) fn emoji(name: &str) -> Option<&'static str> {
+    EMOJI
+        .binary_search_by_key(&name, |&(name, _)| name)
+        .ok()
+        .map(|index| EMOJI[index].1)
+}
+
+/// The names starting with `prefix`, in order
+pub(crate) fn emoji_names(prefix: &str) -> impl Iterator<Item = &'static str> + '_ {
+    let start = EMOJI.partition_point(|&(name, _)| name < prefix);
+    EMOJI[start..]
+        .iter()
+        .map(|&(name, _)| name)
+        .take_while(move |name| name.starts_with(prefix))
+}
+
+#[test]
+fn emoji_test() {
+    assert!(EMOJI.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    assert_eq!(emoji("blush"), Some("\u{1F60A}"));
+    assert_eq!(emoji("heart"), Some("\u{2764}\u{FE0F}"));
+    assert_eq!(emoji("smil"), None);
+    assert_eq!(
+        emoji_names("smil").collect::<Vec<_>>(),
+        ["smile", "smiley", "smiling_imp"]
+    );
+}
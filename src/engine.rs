@@ -1,26 +1,62 @@
 use std::io::{Stdout, Write};
 
 use crossterm::{
-    cursor::{position, MoveToColumn, RestorePosition, SavePosition},
-    event::{read, Event, KeyCode, KeyEvent, KeyModifiers},
-    style::{Color, Print, ResetColor, SetForegroundColor},
-    terminal::{Clear, ClearType},
-    ExecutableCommand, QueueableCommand, Result,
+    cursor::{
+        position, CursorShape, MoveTo, MoveToColumn, MoveUp, RestorePosition, SavePosition,
+        SetCursorShape,
+    },
+    event::{poll, read, Event, KeyCode, KeyEvent, KeyModifiers},
+    style::{Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor},
+    terminal::{size, Clear, ClearType},
+    QueueableCommand,
 };
 
-use std::collections::VecDeque;
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
 
-use crate::line_buffer::LineBuffer;
+use std::{
+    borrow::Cow,
+    collections::{HashMap, VecDeque},
+    ops::Range,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::config::Config;
+use crate::constraints::{InputConstraints, InvalidInputFeedback};
+use crate::error::Result;
+use crate::keybindings::{key_sequence_to_string, Binding, KeySequenceMatch, Keybindings};
+use crate::line_buffer::{
+    display_width, truncate_to_width, zero_string, CharSearch, LineBuffer, WordDefinition,
+};
+use crate::terminal::{self, RawModeGuard, DEFAULT_CURSOR_SHAPE};
 
-const HISTORY_SIZE: usize = 100;
+const MATCHING_BRACKET_COLOR: Color = Color::Cyan;
+const UNMATCHED_BRACKET_COLOR: Color = Color::Red;
+const INVALID_INPUT_COLOR: Color = Color::Red;
+const INVALID_INPUT_FLASH: Duration = Duration::from_millis(150);
+const PICKER_HEIGHT: u16 = 10;
+// How many changes undo goes back
+const UNDO_LIMIT: usize = 100;
+// Erases the scrollback, crossterm 0.20 has no ClearType for it
+const CLEAR_SCROLLBACK: &str = "\x1b[3J";
+// How often `read_line` checks for messages from an `ExternalPrinter`
+const EXTERNAL_PRINT_INTERVAL: Duration = Duration::from_millis(50);
+// The longest password in bytes, as its buffer is never moved to a bigger allocation
+const PASSWORD_CAPACITY: usize = 1024;
 
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum EditCommand {
     MoveToStart,
     MoveToEnd,
     MoveLeft,
     MoveRight,
-    MoveWordLeft,
-    MoveWordRight,
+    MoveWordLeft(WordDefinition),
+    MoveWordRight(WordDefinition),
     InsertChar(char),
     Backspace,
     Delete,
@@ -30,16 +66,58 @@ pub enum EditCommand {
     Clear,
     CutFromStart,
     CutToEnd,
-    CutWordLeft,
-    CutWordRight,
+    CutWordLeft(WordDefinition),
+    CutWordRight(WordDefinition),
     InsertCutBuffer,
     UppercaseWord,
     LowercaseWord,
     CapitalizeChar,
     SwapWords,
     SwapGraphemes,
+    MoveToChar(CharSearch),
+    CutToChar(CharSearch),
+    RepeatCharSearch,
+    ReverseCharSearch,
+    /// Run the command registered under this name with `Engine::register_command`
+    Custom(String),
+    /// Expand the abbreviation right before the insertion point, if there is one
+    ExpandAbbreviation,
+    /// Undo the last change to the buffer
+    Undo,
+    /// Turn a code point like `U+1F60A` right before the insertion point into its character
+    ExpandCodePoint,
+    /// Turn a `:shortcode:` right before the insertion point into its emoji
+    ExpandShortcode,
+    /// Complete a `:shortcode` right before the insertion point
+    CompleteShortcode,
+    /// Switch between inserting and typing over the grapheme under the cursor
+    ToggleOverwrite,
+}
+
+/// A buffer transformation supplied by the application
+pub type CustomCommand = Arc<dyn Fn(&mut LineBuffer) + Send + Sync>;
+
+/// What a submit hook decides about the line being submitted
+pub enum SubmitDecision {
+    Accept,
+    /// Submit this instead, and put it in the history in place of the typed line
+    Rewrite(String),
+    /// Keep editing the line
+    Reject,
+}
+
+/// How `Engine::read_password` shows what is typed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PasswordMask {
+    /// One of this character per grapheme typed
+    Char(char),
+    /// Nothing at all, the cursor stays put
+    Hidden,
 }
 
+/// Checks a line before it is submitted, see `Engine::add_submit_hook`
+pub type SubmitHook = Arc<dyn Fn(&str) -> SubmitDecision + Send + Sync>;
+
 pub struct Engine {
     line_buffer: LineBuffer,
 
@@ -50,12 +128,170 @@ pub struct Engine {
     history: VecDeque<String>,
     history_cursor: i64,
     has_history: bool,
+    history_size: usize,
+
+    // Last character search, for repeating it
+    last_char_search: Option<CharSearch>,
+
+    // Keybindings
+    keybindings: Keybindings,
+    chord_timeout: Duration,
+
+    // Application defined commands, by name
+    custom_commands: HashMap<String, CustomCommand>,
+    submit_hooks: Vec<SubmitHook>,
+
+    // Abbreviations, expanded on space and enter
+    abbreviations: HashMap<String, String>,
+
+    // Buffer and insertion point before each change, for undo, and the character
+    // typed last if the last change was typing one, for merging typed words
+    undo_stack: Vec<(String, usize)>,
+    last_typed: Option<char>,
+
+    // Colors
+    prompt_color: Color,
+    chord_color: Color,
+
+    // Insert closing brackets and quotes along with the opening ones
+    auto_pair: bool,
+
+    // Whether completion matches `:Smi` to `:smile`
+    completion_ignore_case: bool,
+
+    // Whether typed graphemes replace the one under the cursor, and where each
+    // one typed over went, what it replaced and its length, for backspace
+    overwrite: bool,
+    overwritten: Vec<(usize, String, usize)>,
+
+    // Set while reading a password
+    password_mask: Option<PasswordMask>,
+
+    // Set by `Binding::ReadChar` and `Binding::QuotedInsert`, which take the
+    // next key press as their argument
+    awaiting_argument: Option<Binding>,
+
+    // Whether Ctrl-Z suspends, and whether we were just continued after it
+    suspend: bool,
+    resumed: bool,
+
+    // Set by `Binding::ClearScreen` until the screen is cleared, to whether
+    // the scrollback goes too
+    clear_screen: Option<bool>,
+
+    // Set while reading with constraints, and whether input was just refused
+    constraints: Option<InputConstraints>,
+    input_rejected: bool,
+
+    // Messages from other threads, waiting to be printed above the prompt
+    external_messages: Arc<Mutex<Vec<String>>>,
 }
 
 pub enum Signal {
     Success(String),
-    CtrlC, // Interrupt current editing
-    CtrlD, // End terminal session
+    CtrlC,               // Interrupt current editing
+    CtrlD,               // End terminal session
+    Host(String),        // Application defined, the buffer is kept for the next read_line
+    Timeout(String),     // The deadline passed, with what was typed so far
+    Interrupted(String), // The interrupt flag was set, with what was typed so far
+}
+
+/// Prints above the prompt while `read_line` is running, from any thread.
+/// Clones print to the same engine.
+#[derive(Clone)]
+pub struct ExternalPrinter {
+    messages: Arc<Mutex<Vec<String>>>,
+}
+
+impl ExternalPrinter {
+    /// Queue `message` to be printed by the running or next `read_line`
+    pub fn print<S: Into<String>>(&self, message: S) {
+        let message = message.into();
+        match self.messages.lock() {
+            Ok(mut messages) => messages.push(message),
+            Err(poisoned) => poisoned.into_inner().push(message),
+        }
+    }
+}
+
+/// How `Engine::read_selection` ended
+#[derive(Debug, PartialEq)]
+pub enum Selection {
+    /// Indices of the chosen items, in list order. Just one without multi-select.
+    Selected(Vec<usize>),
+    CtrlC,
+    CtrlD,
+}
+
+/// The state of the list shown by `Engine::read_selection`
+struct Picker {
+    items: Vec<String>,
+    // Indices of the items matching the filter
+    matches: Vec<usize>,
+    // Index into `matches` of the highlighted row, and of the first row shown
+    highlighted: usize,
+    scroll: usize,
+    height: usize,
+    multi_select: bool,
+    selected: Vec<bool>,
+}
+
+impl Picker {
+    fn new(items: Vec<String>, height: usize, multi_select: bool) -> Picker {
+        let selected = vec![false; items.len()];
+        let matches = (0..items.len()).collect();
+        Picker {
+            items,
+            matches,
+            highlighted: 0,
+            scroll: 0,
+            height,
+            multi_select,
+            selected,
+        }
+    }
+
+    /// Only show the items containing `filter`, ignoring case
+    fn filter(&mut self, filter: &str) {
+        let filter = filter.to_lowercase();
+        self.matches = (0..self.items.len())
+            .filter(|&index| self.items[index].to_lowercase().contains(&filter))
+            .collect();
+        self.highlighted = 0;
+        self.scroll = 0;
+    }
+
+    /// Move the highlight by `delta` rows, scrolling to keep it in view
+    fn move_highlight(&mut self, delta: isize) {
+        let last = self.matches.len().saturating_sub(1) as isize;
+        self.highlighted = (self.highlighted as isize + delta).clamp(0, last) as usize;
+        if self.highlighted < self.scroll {
+            self.scroll = self.highlighted;
+        } else if self.highlighted >= self.scroll + self.height {
+            self.scroll = self.highlighted + 1 - self.height;
+        }
+    }
+
+    fn toggle_highlighted(&mut self) {
+        if let Some(&index) = self.matches.get(self.highlighted) {
+            self.selected[index] = !self.selected[index];
+        }
+    }
+
+    /// What Enter picks: the selected items, or else the highlighted one
+    fn selection(&self) -> Vec<usize> {
+        let selected: Vec<usize> = (0..self.items.len())
+            .filter(|&index| self.selected[index])
+            .collect();
+        if !selected.is_empty() {
+            return selected;
+        }
+        self.matches
+            .get(self.highlighted)
+            .copied()
+            .into_iter()
+            .collect()
+    }
 }
 
 pub fn print_message(stdout: &mut Stdout, msg: &str) -> Result<()> {
@@ -77,21 +313,182 @@ pub fn print_crlf(stdout: &mut Stdout) -> Result<()> {
     Ok(())
 }
 
-fn buffer_repaint(stdout: &mut Stdout, engine: &Engine, prompt_offset: u16) -> Result<()> {
+/// The closing counterpart auto-pairing inserts along with `c`
+fn auto_pair_closer(c: char) -> Option<char> {
+    match c {
+        '(' => Some(')'),
+        '[' => Some(']'),
+        '{' => Some('}'),
+        '"' => Some('"'),
+        '\'' => Some('\''),
+        _ => None,
+    }
+}
+
+fn is_auto_pair_closer(c: char) -> bool {
+    matches!(c, ')' | ']' | '}' | '"' | '\'')
+}
+
+/// The character of a key press if it is a plain one. Used by commands that
+/// take a character argument, like character search.
+fn plain_char(KeyEvent { code, modifiers }: KeyEvent) -> Option<char> {
+    match code {
+        KeyCode::Char(c) if !modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+            Some(c)
+        }
+        _ => None,
+    }
+}
+
+/// The character a key press stands for, like `'\t'` for Tab or `'\x01'` for
+/// Ctrl-A. Used by quoted insert.
+fn verbatim_char(KeyEvent { code, modifiers }: KeyEvent) -> Option<char> {
+    if modifiers.contains(KeyModifiers::ALT) {
+        return None;
+    }
+    match code {
+        KeyCode::Char(c) if modifiers.contains(KeyModifiers::CONTROL) => match c {
+            'a'..='z' | 'A'..='Z' | '@' | '[' | '\\' | ']' | '^' | '_' => {
+                Some((c.to_ascii_uppercase() as u8 & 0x1f) as char)
+            }
+            ' ' => Some('\0'),
+            // Ctrl-\ Ctrl-] Ctrl-^ and Ctrl-_ come in as Ctrl-4 to Ctrl-7
+            '4'..='7' => Some((c as u8 - b'4' + 0x1c) as char),
+            _ => None,
+        },
+        KeyCode::Char(c) => Some(c),
+        KeyCode::Tab => Some('\t'),
+        KeyCode::Enter => Some('\r'),
+        KeyCode::Esc => Some('\x1b'),
+        KeyCode::Backspace => Some('\x7f'),
+        _ => None,
+    }
+}
+
+/// The character typed by a change from `before` to `after`, if all it did was
+/// insert one at the insertion point and move past it, like a plain key press
+fn typed_char(before: &(String, usize), after: &str, after_insertion_point: usize) -> Option<char> {
+    let (before, insertion_point) = (&before.0, before.1);
+    let typed = after.get(insertion_point..after_insertion_point)?;
+    let mut chars = typed.chars();
+    let c = chars.next()?;
+    let only_typed = chars.next().is_none()
+        && after[..insertion_point] == before[..insertion_point]
+        && after[after_insertion_point..] == before[insertion_point..];
+    only_typed.then_some(c)
+}
+
+/// Show control characters in caret notation, like `^I` for a tab, so that
+/// they take up the room on screen the cursor is moved by
+fn caret_notation(text: &str) -> Cow<'_, str> {
+    if !text.chars().any(|c| c.is_ascii_control()) {
+        return Cow::Borrowed(text);
+    }
+    let mut shown = String::with_capacity(text.len() + 1);
+    for c in text.chars() {
+        if c.is_ascii_control() {
+            shown.push('^');
+            shown.push((c as u8 ^ 0x40) as char);
+        } else {
+            shown.push(c);
+        }
+    }
+    Cow::Owned(shown)
+}
+
+/// Print `buffer[range]`, drawing the single character brackets at the
+/// highlighted indices in their color
+fn queue_highlighted(
+    stdout: &mut Stdout,
+    buffer: &str,
+    range: Range<usize>,
+    highlights: &[(usize, Color)],
+) -> Result<()> {
+    let mut start = range.start;
+    for &(index, color) in highlights {
+        if index < range.start || index >= range.end {
+            continue;
+        }
+        stdout
+            .queue(Print(caret_notation(&buffer[start..index])))?
+            .queue(SetForegroundColor(color))?
+            .queue(SetAttribute(Attribute::Bold))?
+            .queue(Print(&buffer[index..index + 1]))?
+            .queue(SetAttribute(Attribute::Reset))?
+            .queue(ResetColor)?;
+        start = index + 1;
+    }
+    stdout.queue(Print(caret_notation(&buffer[start..range.end])))?;
+
+    Ok(())
+}
+
+fn buffer_repaint(
+    stdout: &mut Stdout,
+    engine: &Engine,
+    prompt_offset: u16,
+    pending: &[KeyEvent],
+    prompt_color: Color,
+) -> Result<()> {
     let new_index = engine.get_insertion_point();
 
     // Repaint logic:
     //
-    // Start after the prompt
+    // Start at the prompt, and draw it along with any pending chord
     // Draw the string slice from 0 to the grapheme start left of insertion point
     // Then, get the position on the screen
     // Then draw the remainer of the buffer from above
     // Finally, reset the cursor to the saved position
 
     stdout.queue(MoveToColumn(prompt_offset))?;
-    stdout.queue(Print(&engine.line_buffer[0..new_index]))?;
+    if !pending.is_empty() {
+        stdout
+            .queue(SetForegroundColor(engine.chord_color))?
+            .queue(Print(key_sequence_to_string(pending)))?
+            .queue(Print("-"))?;
+    }
+    stdout
+        .queue(SetForegroundColor(prompt_color))?
+        .queue(Print(if engine.overwrite { "》" } else { "〉" }))?
+        .queue(ResetColor)?;
+
+    if let Some(mask) = engine.password_mask {
+        if let PasswordMask::Char(c) = mask {
+            let masked = |text: &str| c.to_string().repeat(text.graphemes(true).count());
+            stdout.queue(Print(masked(&engine.line_buffer[..new_index])))?;
+            stdout.queue(SavePosition)?;
+            stdout.queue(Print(masked(&engine.line_buffer[new_index..])))?;
+        } else {
+            stdout.queue(SavePosition)?;
+        }
+        stdout.queue(Clear(ClearType::UntilNewLine))?;
+        stdout.queue(RestorePosition)?;
+        stdout.flush()?;
+
+        return Ok(());
+    }
+
+    // Unmatched brackets are red, and so is the pair around the cursor in its own color
+    let brackets = engine.line_buffer.brackets();
+    let mut highlights: Vec<(usize, Color)> = brackets
+        .unmatched
+        .iter()
+        .map(|&index| (index, UNMATCHED_BRACKET_COLOR))
+        .collect();
+    if let Some((open, close)) = engine.line_buffer.matching_brackets(&brackets) {
+        highlights.push((open, MATCHING_BRACKET_COLOR));
+        highlights.push((close, MATCHING_BRACKET_COLOR));
+    }
+    highlights.sort_unstable();
+
+    queue_highlighted(stdout, &engine.line_buffer, 0..new_index, &highlights)?;
     stdout.queue(SavePosition)?;
-    stdout.queue(Print(&engine.line_buffer[new_index..]))?;
+    queue_highlighted(
+        stdout,
+        &engine.line_buffer,
+        new_index..engine.line_buffer.len(),
+        &highlights,
+    )?;
     stdout.queue(Clear(ClearType::UntilNewLine))?;
     stdout.queue(RestorePosition)?;
 
@@ -100,9 +497,130 @@ fn buffer_repaint(stdout: &mut Stdout, engine: &Engine, prompt_offset: u16) -> R
     Ok(())
 }
 
+/// Draw the rows of the picker below the prompt, then the prompt with the filter
+fn picker_repaint(
+    stdout: &mut Stdout,
+    engine: &Engine,
+    picker: &Picker,
+    prompt_offset: u16,
+    prompt_row: u16,
+) -> Result<()> {
+    let (width, _) = size()?;
+
+    for line in 0..picker.height {
+        stdout.queue(MoveTo(0, prompt_row + 1 + line as u16))?;
+        let row = picker.scroll + line;
+        if let Some(&index) = picker.matches.get(row) {
+            let marker = match (picker.multi_select, picker.selected[index]) {
+                (false, _) => "",
+                (true, false) => "[ ] ",
+                (true, true) => "[x] ",
+            };
+            // Wide characters count double, so that no item wraps onto the next row
+            let item = truncate_to_width(
+                &picker.items[index],
+                (width as usize).saturating_sub(marker.len() + 1),
+            );
+            if row == picker.highlighted {
+                stdout.queue(SetAttribute(Attribute::Reverse))?;
+            }
+            stdout
+                .queue(Print(marker))?
+                .queue(Print(caret_notation(item)))?
+                .queue(SetAttribute(Attribute::Reset))?;
+        }
+        stdout.queue(Clear(ClearType::UntilNewLine))?;
+    }
+
+    stdout.queue(MoveTo(0, prompt_row))?;
+    buffer_repaint(stdout, engine, prompt_offset, &[], engine.prompt_color)
+}
+
+/// Where a `read_line` is at, besides the line itself
+pub(crate) struct LineState {
+    pub(crate) prompt_offset: u16,
+    pub(crate) pending: Vec<KeyEvent>,
+    // When a pending chord falls back to the binding of the keys typed so far
+    chord_deadline: Option<Instant>,
+    // A binding waiting for the next key press, which is its argument
+    argument: Option<Binding>,
+    // Until when the prompt is drawn in the invalid input color
+    flash_deadline: Option<Instant>,
+    // Whether the cursor is drawn as a block, for overwrite mode
+    block_cursor: bool,
+    // Set by a binding until the screen is cleared, to whether the scrollback goes too
+    clear_screen: Option<bool>,
+    // Set after being continued until the prompt starts over where the cursor is
+    resumed: bool,
+}
+
+impl LineState {
+    /// Start a line with the prompt at the cursor
+    pub(crate) fn new() -> Result<LineState> {
+        let (column, _) = position()?;
+        Ok(LineState::at_column(column))
+    }
+
+    pub(crate) fn at_column(column: u16) -> LineState {
+        LineState {
+            prompt_offset: column + 1,
+            pending: Vec::new(),
+            chord_deadline: None,
+            argument: None,
+            flash_deadline: None,
+            block_cursor: false,
+            clear_screen: None,
+            resumed: false,
+        }
+    }
+
+    /// Draw the cursor as a block, or in the terminal's own shape again
+    pub(crate) fn set_block_cursor(&mut self, stdout: &mut Stdout, block: bool) -> Result<()> {
+        if self.block_cursor != block {
+            match block {
+                true => stdout.queue(SetCursorShape(CursorShape::Block))?,
+                false => stdout.queue(Print(DEFAULT_CURSOR_SHAPE))?,
+            };
+            stdout.flush()?;
+            self.block_cursor = block;
+        }
+        Ok(())
+    }
+
+    /// Stop drawing the prompt in the invalid input color once the flash is
+    /// over, returning whether it was
+    fn end_flash(&mut self) -> bool {
+        let over = self
+            .flash_deadline
+            .is_some_and(|deadline| Instant::now() >= deadline);
+        if over {
+            self.flash_deadline = None;
+        }
+        over
+    }
+
+    /// How long to wait for an event before waking up anyway, to print messages
+    /// from other threads and check on the chord, the flash and `deadline`
+    pub(crate) fn wake_up_timeout(&self, deadline: Option<Instant>) -> Duration {
+        let mut timeout = EXTERNAL_PRINT_INTERVAL;
+        let deadlines = [self.chord_deadline, self.flash_deadline, deadline];
+        for deadline in deadlines.iter().flatten() {
+            timeout = timeout.min(deadline.saturating_duration_since(Instant::now()));
+        }
+        timeout
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Engine {
     pub fn new() -> Engine {
-        let history = VecDeque::with_capacity(HISTORY_SIZE);
+        let config = Config::default();
+        let history = VecDeque::with_capacity(config.history_size);
         let history_cursor = -1i64;
         let has_history = false;
         let cut_buffer = String::new();
@@ -113,11 +631,103 @@ impl Engine {
             history,
             history_cursor,
             has_history,
+            history_size: config.history_size,
+            last_char_search: None,
+            keybindings: Keybindings::emacs(),
+            chord_timeout: Duration::from_millis(config.chord_timeout),
+            custom_commands: HashMap::new(),
+            submit_hooks: Vec::new(),
+            abbreviations: HashMap::new(),
+            undo_stack: Vec::new(),
+            last_typed: None,
+            prompt_color: config.prompt_color,
+            chord_color: config.chord_color,
+            auto_pair: config.auto_pair,
+            completion_ignore_case: false,
+            overwrite: false,
+            overwritten: Vec::new(),
+            password_mask: None,
+            awaiting_argument: None,
+            suspend: config.suspend,
+            resumed: false,
+            clear_screen: None,
+            constraints: None,
+            input_rejected: false,
+            external_messages: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
+    /// Read a TOML config file and apply it, see `Config`
+    pub fn load_config(&mut self, path: &Path) -> Result<()> {
+        let config = Config::load(path)?;
+        self.apply_config(&config);
+        Ok(())
+    }
+
+    pub fn apply_config(&mut self, config: &Config) {
+        self.history_size = config.history_size;
+        self.history.truncate(config.history_size);
+        self.chord_timeout = Duration::from_millis(config.chord_timeout);
+        self.prompt_color = config.prompt_color;
+        self.chord_color = config.chord_color;
+        self.auto_pair = config.auto_pair;
+        self.suspend = config.suspend;
+        self.keybindings.extend(&config.keybindings);
+        self.abbreviations.extend(config.abbreviations.clone());
+    }
+
     pub fn run_edit_commands(&mut self, commands: &[EditCommand]) {
+        // Every call is one step for undo, however many commands it runs
+        let before = (self.line_buffer.to_string(), self.get_insertion_point());
+
         for command in commands {
+            // A password must not leak into the history, or get replaced by it
+            if self.password_mask.is_some()
+                && matches!(
+                    command,
+                    EditCommand::AppendToHistory
+                        | EditCommand::PreviousHistory
+                        | EditCommand::NextHistory
+                        | EditCommand::ExpandAbbreviation
+                        | EditCommand::ExpandShortcode
+                        | EditCommand::CompleteShortcode
+                        | EditCommand::Undo
+                )
+            {
+                continue;
+            }
+
+            // Refuse characters the constraints don't allow, and everything after them
+            let allowed = match (&self.constraints, command) {
+                (Some(constraints), EditCommand::InsertChar(c)) => constraints.allows_char(*c),
+                (Some(constraints), EditCommand::InsertCutBuffer) => {
+                    self.cut_buffer.chars().all(|c| constraints.allows_char(c))
+                }
+                _ => true,
+            };
+            if !allowed {
+                self.input_rejected = true;
+                break;
+            }
+            // Growing a password's buffer would leave a copy of it behind in the
+            // old allocation, so what doesn't fit is refused instead
+            if self.password_mask.is_some() && !self.has_room_for(command) {
+                self.input_rejected = true;
+                break;
+            }
+            // Backspace only restores what was typed over right before it
+            if !matches!(
+                command,
+                EditCommand::InsertChar(_) | EditCommand::MoveRight | EditCommand::Backspace
+            ) {
+                self.overwritten.clear();
+            }
+
+            let command_before = self
+                .constraints
+                .as_ref()
+                .map(|_| (self.line_buffer.to_string(), self.get_insertion_point()));
+
             match command {
                 EditCommand::MoveToStart => self.line_buffer.set_insertion_point(0),
                 EditCommand::MoveToEnd => {
@@ -125,20 +735,70 @@ impl Engine {
                 }
                 EditCommand::MoveLeft => self.line_buffer.move_left(),
                 EditCommand::MoveRight => self.line_buffer.move_right(),
-                EditCommand::MoveWordLeft => {
-                    self.line_buffer.move_word_left();
+                EditCommand::MoveWordLeft(word_definition) => {
+                    self.line_buffer.move_word_left(*word_definition);
                 }
-                EditCommand::MoveWordRight => {
-                    self.line_buffer.move_word_right();
+                EditCommand::MoveWordRight(word_definition) => {
+                    self.line_buffer.move_word_right(*word_definition);
                 }
                 EditCommand::InsertChar(c) => {
                     let insertion_point = self.line_buffer.get_insertion_point();
-                    self.line_buffer.insert_char(insertion_point, *c)
+                    let next_char = self.line_buffer[insertion_point..].chars().next();
+                    let previous_char = self.line_buffer[..insertion_point].chars().next_back();
+
+                    if self.overwrite {
+                        let end = self.line_buffer.grapheme_right_index();
+                        let replaced = self.line_buffer[insertion_point..end].to_string();
+                        self.line_buffer
+                            .replace_range(insertion_point..end, c.encode_utf8(&mut [0; 4]));
+                        // A password must not be kept around in pieces either
+                        if self.password_mask.is_none() {
+                            self.overwritten
+                                .push((insertion_point, replaced, c.len_utf8()));
+                        }
+                    } else if self.auto_pair && is_auto_pair_closer(*c) && next_char == Some(*c) {
+                        // Type over the closer we inserted earlier, the MoveRight
+                        // that follows InsertChar steps over it
+                    } else if let Some(closer) = auto_pair_closer(*c).filter(|_| self.auto_pair) {
+                        // Quotes right after a word are more likely apostrophes
+                        let is_quote = *c == closer;
+                        if is_quote && previous_char.is_some_and(char::is_alphanumeric) {
+                            self.line_buffer.insert_char(insertion_point, *c)
+                        } else {
+                            self.line_buffer.insert_char(insertion_point, closer);
+                            self.line_buffer.insert_char(insertion_point, *c);
+                        }
+                    } else {
+                        self.line_buffer.insert_char(insertion_point, *c)
+                    }
                 }
                 EditCommand::Backspace => {
+                    let insertion_point = self.get_insertion_point();
                     let left_index = self.line_buffer.grapheme_left_index();
-                    if left_index < self.get_insertion_point() {
-                        self.clear_range(left_index..self.get_insertion_point());
+                    let typed_over = self
+                        .overwritten
+                        .last()
+                        .is_some_and(|(start, _, len)| start + len == insertion_point);
+                    if self.overwrite && typed_over {
+                        if let Some((start, replaced, len)) = self.overwritten.pop() {
+                            self.line_buffer
+                                .replace_range(start..start + len, &replaced);
+                            self.set_insertion_point(start);
+                        }
+                    } else if left_index < insertion_point {
+                        self.overwritten.clear();
+                        let mut delete_range = left_index..self.get_insertion_point();
+                        // Deleting the opener of an empty pair deletes the closer too
+                        if self.auto_pair {
+                            let opener = self.line_buffer[delete_range.clone()].chars().next();
+                            let closer = opener.and_then(auto_pair_closer);
+                            if let Some(closer) = closer {
+                                if self.line_buffer[delete_range.end..].starts_with(closer) {
+                                    delete_range.end += closer.len_utf8();
+                                }
+                            }
+                        }
+                        self.clear_range(delete_range);
                         self.set_insertion_point(left_index);
                     }
                 }
@@ -149,11 +809,16 @@ impl Engine {
                     }
                 }
                 EditCommand::Clear => {
-                    self.line_buffer.clear();
+                    // Submitting or abandoning a password must not leave it behind
+                    if self.password_mask.is_some() {
+                        self.line_buffer.clear_and_zero();
+                    } else {
+                        self.line_buffer.clear();
+                    }
                     self.set_insertion_point(0);
                 }
                 EditCommand::AppendToHistory => {
-                    if self.history.len() + 1 == HISTORY_SIZE {
+                    if self.history.len() + 1 >= self.history_size {
                         // History is "full", so we delete the oldest entry first,
                         // before adding a new one.
                         self.history.pop_back();
@@ -208,8 +873,8 @@ impl Engine {
                         self.clear_to_end();
                     }
                 }
-                EditCommand::CutWordLeft => {
-                    let left_index = self.line_buffer.word_left_index();
+                EditCommand::CutWordLeft(word_definition) => {
+                    let left_index = self.line_buffer.word_left_index(*word_definition);
                     if left_index < self.get_insertion_point() {
                         let cut_range = left_index..self.get_insertion_point();
                         self.cut_buffer
@@ -218,8 +883,8 @@ impl Engine {
                         self.set_insertion_point(left_index);
                     }
                 }
-                EditCommand::CutWordRight => {
-                    let right_index = self.line_buffer.word_right_index();
+                EditCommand::CutWordRight(word_definition) => {
+                    let right_index = self.line_buffer.word_right_index(*word_definition);
                     if right_index > self.get_insertion_point() {
                         let cut_range = self.get_insertion_point()..right_index;
                         self.cut_buffer
@@ -233,45 +898,45 @@ impl Engine {
                     self.set_insertion_point(self.get_insertion_point() + self.cut_buffer.len());
                 }
                 EditCommand::UppercaseWord => {
-                    let right_index = self.line_buffer.word_right_index();
+                    let right_index = self.line_buffer.word_right_index(WordDefinition::Unicode);
                     if right_index > self.get_insertion_point() {
                         let change_range = self.get_insertion_point()..right_index;
                         let uppercased = self.line_buffer[change_range.clone()].to_uppercase();
                         self.line_buffer.replace_range(change_range, &uppercased);
-                        self.line_buffer.move_word_right();
+                        self.line_buffer.move_word_right(WordDefinition::Unicode);
                     }
                 }
                 EditCommand::LowercaseWord => {
-                    let right_index = self.line_buffer.word_right_index();
+                    let right_index = self.line_buffer.word_right_index(WordDefinition::Unicode);
                     if right_index > self.get_insertion_point() {
                         let change_range = self.get_insertion_point()..right_index;
                         let lowercased = self.line_buffer[change_range.clone()].to_lowercase();
                         self.line_buffer.replace_range(change_range, &lowercased);
-                        self.line_buffer.move_word_right();
+                        self.line_buffer.move_word_right(WordDefinition::Unicode);
                     }
                 }
                 EditCommand::CapitalizeChar => {
                     if self.line_buffer.on_whitespace() {
-                        self.line_buffer.move_word_right();
-                        self.line_buffer.move_word_left();
+                        self.line_buffer.move_word_right(WordDefinition::Unicode);
+                        self.line_buffer.move_word_left(WordDefinition::Unicode);
                     }
                     let right_index = self.line_buffer.grapheme_right_index();
                     if right_index > self.get_insertion_point() {
                         let change_range = self.get_insertion_point()..right_index;
                         let uppercased = self.line_buffer[change_range.clone()].to_uppercase();
                         self.line_buffer.replace_range(change_range, &uppercased);
-                        self.line_buffer.move_word_right();
+                        self.line_buffer.move_word_right(WordDefinition::Unicode);
                     }
                 }
                 EditCommand::SwapWords => {
                     let old_insertion_point = self.get_insertion_point();
-                    self.line_buffer.move_word_right();
+                    self.line_buffer.move_word_right(WordDefinition::Unicode);
                     let word_2_end = self.get_insertion_point();
-                    self.line_buffer.move_word_left();
+                    self.line_buffer.move_word_left(WordDefinition::Unicode);
                     let word_2_start = self.get_insertion_point();
-                    self.line_buffer.move_word_left();
+                    self.line_buffer.move_word_left(WordDefinition::Unicode);
                     let word_1_start = self.get_insertion_point();
-                    let word_1_end = self.line_buffer.word_right_index();
+                    let word_1_end = self.line_buffer.word_right_index(WordDefinition::Unicode);
 
                     if word_1_start < word_1_end
                         && word_1_end < word_2_start
@@ -312,8 +977,169 @@ impl Engine {
                         self.set_insertion_point(insertion_point);
                     }
                 }
+                EditCommand::MoveToChar(search) => {
+                    self.last_char_search = Some(*search);
+                    self.move_to_char(*search);
+                }
+                EditCommand::CutToChar(search) => {
+                    self.last_char_search = Some(*search);
+                    if let Some(cut_range) = self.line_buffer.char_search_cut_range(*search) {
+                        if !cut_range.is_empty() {
+                            let new_insertion_point = cut_range.start;
+                            self.cut_buffer
+                                .replace_range(.., &self.line_buffer[cut_range.clone()]);
+                            self.clear_range(cut_range);
+                            self.set_insertion_point(new_insertion_point);
+                        }
+                    }
+                }
+                EditCommand::RepeatCharSearch => {
+                    if let Some(search) = self.last_char_search {
+                        self.repeat_char_search(search);
+                    }
+                }
+                EditCommand::ReverseCharSearch => {
+                    if let Some(search) = self.last_char_search {
+                        self.repeat_char_search(search.reversed());
+                    }
+                }
+                EditCommand::Custom(name) => {
+                    if let Some(command) = self.custom_commands.get(name).cloned() {
+                        command(&mut self.line_buffer);
+                        // Don't trust the command to leave a valid insertion point behind
+                        if !self
+                            .line_buffer
+                            .is_char_boundary(self.get_insertion_point())
+                        {
+                            self.move_to_end();
+                        }
+                    }
+                }
+                EditCommand::ExpandAbbreviation => {
+                    self.line_buffer.expand_abbreviation(&self.abbreviations);
+                }
+                EditCommand::ExpandCodePoint => {
+                    self.line_buffer.expand_code_point();
+                }
+                EditCommand::ExpandShortcode => {
+                    self.line_buffer.expand_shortcode();
+                }
+                EditCommand::CompleteShortcode => {
+                    self.line_buffer
+                        .complete_shortcode(self.completion_ignore_case);
+                }
+                EditCommand::ToggleOverwrite => {
+                    self.overwrite = !self.overwrite;
+                }
+                EditCommand::Undo => {
+                    if let Some((buffer, insertion_point)) = self.undo_stack.pop() {
+                        self.set_buffer(buffer);
+                        self.set_insertion_point(insertion_point);
+                    }
+                }
+            }
+
+            // Anything that makes a line too long grow longer is undone
+            if let (Some(constraints), Some((buffer, insertion_point))) =
+                (&self.constraints, command_before)
+            {
+                if !constraints.allows_length(&self.line_buffer)
+                    && self.line_buffer.len() > buffer.len()
+                {
+                    self.set_buffer(buffer);
+                    self.set_insertion_point(insertion_point);
+                    self.overwritten.clear();
+                    self.input_rejected = true;
+                    break;
+                }
+            }
+        }
+
+        let typed = typed_char(&before, &self.line_buffer, self.get_insertion_point());
+        if self.password_mask.is_some() {
+            // Cuts only delete, and there is nothing to undo
+            zero_string(&mut self.cut_buffer);
+            let (mut before, _) = before;
+            zero_string(&mut before);
+        } else if !commands.contains(&EditCommand::Undo) && *self.line_buffer != before.0 {
+            // A typed word and the spaces after it undo in one step
+            let merge = match (self.last_typed, typed) {
+                (Some(last), Some(c)) => !last.is_whitespace() || c.is_whitespace(),
+                _ => false,
+            };
+            if !merge || self.undo_stack.is_empty() {
+                self.undo_stack.push(before);
+                if self.undo_stack.len() > UNDO_LIMIT {
+                    self.undo_stack.remove(0);
+                }
             }
         }
+        self.last_typed = typed;
+    }
+
+    /// Whether `command` can't make the buffer outgrow its allocation
+    fn has_room_for(&self, command: &EditCommand) -> bool {
+        let growth = match command {
+            // With room for the closer, if it gets auto-paired
+            EditCommand::InsertChar(c) => 2 * c.len_utf8(),
+            EditCommand::InsertCutBuffer => self.cut_buffer.len(),
+            _ => 0,
+        };
+        self.line_buffer.len() + growth <= self.line_buffer.capacity()
+    }
+
+    /// Turn auto-pairing of brackets and quotes on or off. It is off by default.
+    pub fn set_auto_pair(&mut self, auto_pair: bool) {
+        self.auto_pair = auto_pair;
+    }
+
+    /// Complete regardless of case, like readline's `completion-ignore-case`.
+    /// Completion is case sensitive by default.
+    pub fn set_completion_ignore_case(&mut self, ignore_case: bool) {
+        self.completion_ignore_case = ignore_case;
+    }
+
+    /// Let Ctrl-Z suspend the process, or not. It does by default.
+    pub fn set_suspend(&mut self, suspend: bool) {
+        self.suspend = suspend;
+    }
+
+    /// Expand `abbreviation` to `expansion` when it is typed as a word followed by
+    /// space or enter, the way fish does
+    pub fn add_abbreviation(&mut self, abbreviation: &str, expansion: &str) {
+        self.abbreviations
+            .insert(abbreviation.to_string(), expansion.to_string());
+    }
+
+    /// Run `hook` on every submitted line before it goes into the history.
+    /// Hooks run in the order they were added, each seeing the previous one's rewrite.
+    pub fn add_submit_hook<F>(&mut self, hook: F)
+    where
+        F: Fn(&str) -> SubmitDecision + Send + Sync + 'static,
+    {
+        self.submit_hooks.push(Arc::new(hook));
+    }
+
+    /// Make `command` available as `EditCommand::Custom(name)`, so it can be
+    /// bound to keys like any built-in command
+    pub fn register_command<F>(&mut self, name: &str, command: F)
+    where
+        F: Fn(&mut LineBuffer) + Send + Sync + 'static,
+    {
+        self.custom_commands
+            .insert(name.to_string(), Arc::new(command));
+    }
+
+    fn move_to_char(&mut self, search: CharSearch) {
+        if let Some(index) = self.line_buffer.char_search_index(search) {
+            self.set_insertion_point(index);
+        }
+    }
+
+    fn repeat_char_search(&mut self, search: CharSearch) {
+        if let Some(index) = self.line_buffer.repeated_char_search_index(search) {
+            self.set_insertion_point(index);
+        }
     }
 
     pub fn set_insertion_point(&mut self, pos: usize) {
@@ -347,167 +1173,1131 @@ impl Engine {
         self.line_buffer.clear_range(range)
     }
 
-    pub fn read_line(&mut self, stdout: &mut Stdout) -> Result<Signal> {
-        // print our prompt
-        stdout
-            .execute(SetForegroundColor(Color::Blue))?
-            .execute(Print("〉"))?
-            .execute(ResetColor)?;
+    pub fn set_keybindings(&mut self, keybindings: Keybindings) {
+        self.keybindings = keybindings;
+    }
+
+    /// How long to wait for the rest of a chord like `C-x C-u` before
+    /// falling back to the binding of the keys typed so far
+    pub fn set_chord_timeout(&mut self, chord_timeout: Duration) {
+        self.chord_timeout = chord_timeout;
+    }
+
+    fn run_binding(&mut self, binding: Binding) -> Result<Option<Signal>> {
+        match binding {
+            Binding::Edit(commands) => self.run_edit_commands(&commands),
+            Binding::ReadChar(_) | Binding::QuotedInsert => {
+                self.awaiting_argument = Some(binding);
+            }
+            Binding::Submit => {
+                self.run_edit_commands(&[EditCommand::ExpandAbbreviation]);
+
+                if let Some(constraints) = &self.constraints {
+                    if !constraints.accepts(&self.line_buffer) {
+                        self.input_rejected = true;
+                        return Ok(None);
+                    }
+                }
+
+                // Hooks are for lines, not passwords
+                let hooks = match self.password_mask {
+                    Some(_) => Vec::new(),
+                    None => self.submit_hooks.clone(),
+                };
+                for hook in hooks {
+                    match hook(&self.line_buffer) {
+                        SubmitDecision::Accept => {}
+                        SubmitDecision::Rewrite(buffer) => {
+                            self.set_buffer(buffer);
+                            self.move_to_end();
+                        }
+                        SubmitDecision::Reject => return Ok(None),
+                    }
+                }
+
+                let buffer = self.line_buffer.to_owned();
+
+                self.run_edit_commands(&[EditCommand::AppendToHistory, EditCommand::Clear]);
+                self.undo_stack.clear();
+
+                return Ok(Some(Signal::Success(buffer)));
+            }
+            Binding::Host(name) => return Ok(Some(Signal::Host(name))),
+            Binding::ClearScreen { scrollback } => self.clear_screen = Some(scrollback),
+            Binding::Suspend => {
+                if self.suspend {
+                    terminal::suspend()?;
+                    self.resumed = true;
+                }
+            }
+            Binding::Interrupt => {
+                self.run_edit_commands(&[EditCommand::Clear]);
+                self.undo_stack.clear();
+                return Ok(Some(Signal::CtrlC));
+            }
+            Binding::DeleteOrEndOfFile => {
+                if self.line_buffer.is_empty() {
+                    return Ok(Some(Signal::CtrlD));
+                } else {
+                    self.run_edit_commands(&[EditCommand::Delete]);
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Feed a key into the pending chord and run whatever it completes
+    fn handle_key(&mut self, pending: &mut Vec<KeyEvent>, key: KeyEvent) -> Result<Option<Signal>> {
+        pending.push(key);
+
+        let binding = match self.keybindings.find_binding(pending) {
+            KeySequenceMatch::Prefix(_) => return Ok(None),
+            KeySequenceMatch::Exact(binding) => Some(binding.clone()),
+            KeySequenceMatch::NoMatch => None,
+        };
+        if let Some(binding) = binding {
+            pending.clear();
+            return self.run_binding(binding);
+        }
+
+        pending.pop();
+        if pending.is_empty() {
+            // Unbound keys insert themselves, as long as they are plain characters
+            if let KeyCode::Char(c) = key.code {
+                if !key
+                    .modifiers
+                    .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+                {
+                    self.run_edit_commands(&[EditCommand::InsertChar(c), EditCommand::MoveRight]);
+                }
+            }
+            Ok(None)
+        } else {
+            // The chord was broken off, so the prefix acts on its own and the
+            // key is handled as if it started a new sequence, or as the
+            // argument of the prefix
+            if let Some(signal) = self.flush_pending(pending)? {
+                return Ok(Some(signal));
+            }
+            match self.awaiting_argument.take() {
+                Some(binding) => {
+                    self.run_binding_with_argument(binding, key);
+                    Ok(None)
+                }
+                None => self.handle_key(pending, key),
+            }
+        }
+    }
+
+    /// Finish a `Binding::ReadChar` or `Binding::QuotedInsert` with the key
+    /// pressed after it
+    fn run_binding_with_argument(&mut self, binding: Binding, key: KeyEvent) {
+        match binding {
+            Binding::ReadChar(char_argument) => {
+                if let Some(c) = plain_char(key) {
+                    self.run_edit_commands(&[char_argument.edit_command(c)]);
+                }
+            }
+            Binding::QuotedInsert => {
+                if let Some(c) = verbatim_char(key) {
+                    self.run_edit_commands(&[EditCommand::InsertChar(c), EditCommand::MoveRight]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Run the binding of an incomplete chord, if the prefix is bound on its own
+    fn flush_pending(&mut self, pending: &mut Vec<KeyEvent>) -> Result<Option<Signal>> {
+        let binding = match self.keybindings.find_binding(pending) {
+            KeySequenceMatch::Prefix(binding) => binding.cloned(),
+            _ => None,
+        };
+        pending.clear();
+
+        match binding {
+            Some(binding) => self.run_binding(binding),
+            None => Ok(None),
+        }
+    }
 
-        // set where the input begins
+    /// Read a secret. What is typed is shown masked, never enters the history,
+    /// cut buffer or undo, and is zeroed in the engine once it is returned.
+    /// Input past 1024 bytes is refused.
+    /// The line that was being edited before is left untouched.
+    pub fn read_password(&mut self, stdout: &mut Stdout, mask: PasswordMask) -> Result<Signal> {
+        let line_buffer = std::mem::replace(
+            &mut self.line_buffer,
+            LineBuffer::with_capacity(PASSWORD_CAPACITY),
+        );
+        let cut_buffer = std::mem::take(&mut self.cut_buffer);
+        self.password_mask = Some(mask);
+
+        let signal = self.read_line(stdout);
+
+        self.password_mask = None;
+        self.line_buffer.clear_and_zero();
+        self.line_buffer = line_buffer;
+        self.cut_buffer = cut_buffer;
+
+        signal
+    }
+
+    /// Like `read_line`, but start out with `initial` in the buffer instead of an
+    /// empty line, for editing an existing value. `insertion_point` is a byte
+    /// index into `initial`, moved back to a grapheme boundary if needed.
+    pub fn read_line_with_initial(
+        &mut self,
+        stdout: &mut Stdout,
+        initial: &str,
+        insertion_point: usize,
+    ) -> Result<Signal> {
+        self.set_buffer(initial.to_string());
+        self.line_buffer
+            .set_insertion_point_clamped(insertion_point);
+        self.undo_stack.clear();
+
+        self.read_line(stdout)
+    }
+
+    /// Like `read_line`, but refuse input that breaks `constraints` and keep
+    /// editing when Enter is pressed on a line they don't accept
+    pub fn read_line_with_constraints(
+        &mut self,
+        stdout: &mut Stdout,
+        constraints: &InputConstraints,
+    ) -> Result<Signal> {
+        self.constraints = Some(constraints.clone());
+
+        let signal = self.read_line(stdout);
+
+        self.constraints = None;
+        self.input_rejected = false;
+
+        signal
+    }
+
+    /// Show `prompt` followed by the accepted keys, like `Overwrite? [y/N]`, and
+    /// wait for one of them without needing Enter. Enter picks `default`, which is
+    /// shown in uppercase in the prompt color. Keys are matched case-insensitively
+    /// and the choice comes back as `Signal::Success` holding that one key.
+    pub fn read_choice(
+        &mut self,
+        stdout: &mut Stdout,
+        prompt: &str,
+        choices: &[char],
+        default: Option<char>,
+    ) -> Result<Signal> {
+        let _raw_mode = RawModeGuard::new()?;
+
+        stdout.queue(Print(prompt))?.queue(Print(" ["))?;
+        for (i, &choice) in choices.iter().enumerate() {
+            if i > 0 {
+                stdout.queue(Print("/"))?;
+            }
+            if Some(choice) == default {
+                stdout
+                    .queue(SetForegroundColor(self.prompt_color))?
+                    .queue(SetAttribute(Attribute::Bold))?
+                    .queue(Print(choice.to_uppercase()))?
+                    .queue(SetAttribute(Attribute::Reset))?
+                    .queue(ResetColor)?;
+            } else {
+                stdout.queue(Print(choice))?;
+            }
+        }
+        stdout.queue(Print("] "))?;
+        stdout.flush()?;
+
+        loop {
+            let key = match read()? {
+                Event::Key(key) => key,
+                _ => continue,
+            };
+
+            match self.choose(key, choices, default) {
+                Some(Signal::Success(choice)) => {
+                    stdout.queue(Print(&choice))?;
+                    stdout.flush()?;
+                    return Ok(Signal::Success(choice));
+                }
+                Some(signal) => return Ok(signal),
+                None => {
+                    stdout.queue(Print("\x07"))?;
+                    stdout.flush()?;
+                }
+            }
+        }
+    }
+
+    /// What a key press answers to `read_choice`, or `None` if it isn't one of
+    /// the choices
+    fn choose(&self, key: KeyEvent, choices: &[char], default: Option<char>) -> Option<Signal> {
+        // Interrupting and quitting work on whatever keys they are bound to
+        let choice = match self.keybindings.find_binding(&[key]) {
+            KeySequenceMatch::Exact(Binding::Interrupt) => return Some(Signal::CtrlC),
+            KeySequenceMatch::Exact(Binding::DeleteOrEndOfFile) => return Some(Signal::CtrlD),
+            KeySequenceMatch::Exact(Binding::Submit) => default,
+            _ => plain_char(key).and_then(|c| {
+                choices
+                    .iter()
+                    .copied()
+                    .find(|choice| choice.to_lowercase().eq(c.to_lowercase()))
+            }),
+        };
+
+        choice.map(|choice| Signal::Success(choice.to_string()))
+    }
+
+    /// Ask a yes/no question, see `read_choice`. Answers `Signal::Success` with
+    /// `"y"` or `"n"`.
+    pub fn read_confirmation(
+        &mut self,
+        stdout: &mut Stdout,
+        prompt: &str,
+        default: bool,
+    ) -> Result<Signal> {
+        let default = if default { 'y' } else { 'n' };
+        self.read_choice(stdout, prompt, &['y', 'n'], Some(default))
+    }
+
+    /// Let the user pick from `items` in a list shown below the prompt. Arrows and
+    /// PageUp/PageDown move the highlight and typing filters the list. With
+    /// `multi_select`, Space toggles the highlighted item and Enter picks all
+    /// toggled ones. The line that was being edited before is left untouched.
+    pub fn read_selection<S: AsRef<str>>(
+        &mut self,
+        stdout: &mut Stdout,
+        items: &[S],
+        multi_select: bool,
+    ) -> Result<Selection> {
+        let _raw_mode = RawModeGuard::new()?;
+
+        let items: Vec<String> = items.iter().map(|item| item.as_ref().to_string()).collect();
+        let (_, rows) = size()?;
+        let height = PICKER_HEIGHT
+            .min(rows.saturating_sub(1))
+            .min(items.len() as u16);
+        let mut picker = Picker::new(items, height as usize, multi_select);
+
+        let line_buffer = std::mem::take(&mut self.line_buffer);
+        let undo_stack = std::mem::take(&mut self.undo_stack);
+
+        let selection = self.run_picker(stdout, &mut picker);
+
+        self.line_buffer = line_buffer;
+        self.undo_stack = undo_stack;
+        self.last_typed = None;
+
+        selection
+    }
+
+    /// Edit the filter of a picker with a key press. It is edited like any line,
+    /// minus the history and what would turn the filter into something else
+    /// than what was typed, like emoji or overwriting.
+    fn edit_picker_filter(&mut self, key: KeyEvent) {
+        match self.keybindings.find_binding(&[key]) {
+            KeySequenceMatch::Exact(Binding::Edit(commands)) => {
+                let commands: Vec<EditCommand> = commands
+                    .iter()
+                    .filter(|command| {
+                        !matches!(
+                            command,
+                            EditCommand::AppendToHistory
+                                | EditCommand::PreviousHistory
+                                | EditCommand::NextHistory
+                                | EditCommand::ExpandAbbreviation
+                                | EditCommand::ExpandCodePoint
+                                | EditCommand::ExpandShortcode
+                                | EditCommand::CompleteShortcode
+                                | EditCommand::ToggleOverwrite
+                        )
+                    })
+                    .cloned()
+                    .collect();
+                self.run_edit_commands(&commands);
+            }
+            KeySequenceMatch::NoMatch => {
+                if let Some(c) = plain_char(key) {
+                    self.run_edit_commands(&[EditCommand::InsertChar(c), EditCommand::MoveRight]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn run_picker(&mut self, stdout: &mut Stdout, picker: &mut Picker) -> Result<Selection> {
         let (mut prompt_offset, _) = position()?;
         prompt_offset += 1;
 
-        loop {
-            match read()? {
-                Event::Key(KeyEvent {
-                    code,
-                    modifiers: KeyModifiers::CONTROL,
-                }) => match code {
-                    KeyCode::Char('d') => {
-                        if self.line_buffer.is_empty() {
-                            return Ok(Signal::CtrlD);
-                        } else {
+        // Make room for the list, scrolling the screen up if we are at the bottom
+        if picker.height > 0 {
+            stdout
+                .queue(Print("\n".repeat(picker.height)))?
+                .queue(MoveUp(picker.height as u16))?;
+            stdout.flush()?;
+        }
+        let (_, prompt_row) = position()?;
+
+        let selection = loop {
+            picker_repaint(stdout, self, picker, prompt_offset, prompt_row)?;
+
+            let key = match read()? {
+                Event::Key(key) => key,
+                _ => continue,
+            };
+            let page = picker.height.max(1) as isize;
+            match key.code {
+                KeyCode::Up => picker.move_highlight(-1),
+                KeyCode::Down => picker.move_highlight(1),
+                KeyCode::PageUp => picker.move_highlight(-page),
+                KeyCode::PageDown => picker.move_highlight(page),
+                KeyCode::Char(' ') if picker.multi_select && key.modifiers.is_empty() => {
+                    picker.toggle_highlighted()
+                }
+                _ => {
+                    let filter = self.line_buffer.to_string();
+                    match self.keybindings.find_binding(&[key]) {
+                        KeySequenceMatch::Exact(Binding::Submit) => {
+                            let selection = picker.selection();
+                            if !selection.is_empty() {
+                                break Selection::Selected(selection);
+                            }
+                            stdout.queue(Print("\x07"))?;
+                        }
+                        KeySequenceMatch::Exact(Binding::Interrupt) => break Selection::CtrlC,
+                        KeySequenceMatch::Exact(Binding::DeleteOrEndOfFile) => {
+                            if self.line_buffer.is_empty() {
+                                break Selection::CtrlD;
+                            }
                             self.run_edit_commands(&[EditCommand::Delete]);
                         }
+                        _ => self.edit_picker_filter(key),
                     }
-                    KeyCode::Char('a') => {
-                        self.run_edit_commands(&[EditCommand::MoveToStart]);
-                    }
-                    KeyCode::Char('e') => {
-                        self.run_edit_commands(&[EditCommand::MoveToEnd]);
-                    }
-                    KeyCode::Char('k') => {
-                        self.run_edit_commands(&[EditCommand::CutToEnd]);
-                    }
-                    KeyCode::Char('u') => {
-                        self.run_edit_commands(&[EditCommand::CutFromStart]);
-                    }
-                    KeyCode::Char('y') => {
-                        self.run_edit_commands(&[EditCommand::InsertCutBuffer]);
-                    }
-                    KeyCode::Char('b') => {
-                        self.run_edit_commands(&[EditCommand::MoveLeft]);
-                    }
-                    KeyCode::Char('f') => {
-                        self.run_edit_commands(&[EditCommand::MoveRight]);
-                    }
-                    KeyCode::Char('c') => {
-                        self.run_edit_commands(&[EditCommand::Clear]);
-                        return Ok(Signal::CtrlC);
-                    }
-                    KeyCode::Char('h') => {
-                        self.run_edit_commands(&[EditCommand::Backspace]);
-                    }
-                    KeyCode::Char('w') => {
-                        self.run_edit_commands(&[EditCommand::CutWordLeft]);
+                    if *self.line_buffer != filter {
+                        picker.filter(&self.line_buffer);
                     }
-                    KeyCode::Left => {
-                        self.run_edit_commands(&[EditCommand::MoveWordLeft]);
-                    }
-                    KeyCode::Right => {
-                        self.run_edit_commands(&[EditCommand::MoveWordRight]);
-                    }
-                    KeyCode::Char('p') => {
-                        self.run_edit_commands(&[EditCommand::PreviousHistory]);
-                    }
-                    KeyCode::Char('n') => {
-                        self.run_edit_commands(&[EditCommand::NextHistory]);
-                    }
-                    KeyCode::Char('t') => {
-                        self.run_edit_commands(&[EditCommand::SwapGraphemes]);
-                    }
-                    _ => {}
-                },
-                Event::Key(KeyEvent {
-                    code,
-                    modifiers: KeyModifiers::ALT,
-                }) => match code {
-                    KeyCode::Char('b') => {
-                        self.run_edit_commands(&[EditCommand::MoveWordLeft]);
-                    }
-                    KeyCode::Char('f') => {
-                        self.run_edit_commands(&[EditCommand::MoveWordRight]);
-                    }
-                    KeyCode::Char('d') => {
-                        self.run_edit_commands(&[EditCommand::CutWordRight]);
-                    }
-                    KeyCode::Left => {
-                        self.run_edit_commands(&[EditCommand::MoveWordLeft]);
-                    }
-                    KeyCode::Right => {
-                        self.run_edit_commands(&[EditCommand::MoveWordRight]);
-                    }
-                    KeyCode::Char('u') => {
-                        self.run_edit_commands(&[EditCommand::UppercaseWord]);
-                    }
-                    KeyCode::Char('l') => {
-                        self.run_edit_commands(&[EditCommand::LowercaseWord]);
-                    }
-                    KeyCode::Char('c') => {
-                        self.run_edit_commands(&[EditCommand::CapitalizeChar]);
-                    }
-                    KeyCode::Char('t') => {
-                        self.run_edit_commands(&[EditCommand::SwapWords]);
-                    }
-                    _ => {}
-                },
-                Event::Key(KeyEvent { code, modifiers: _ }) => {
-                    match code {
-                        KeyCode::Char(c) => {
-                            self.run_edit_commands(&[
-                                EditCommand::InsertChar(c),
-                                EditCommand::MoveRight,
-                            ]);
-                        }
-                        KeyCode::Backspace => {
-                            self.run_edit_commands(&[EditCommand::Backspace]);
-                        }
-                        KeyCode::Delete => {
-                            self.run_edit_commands(&[EditCommand::Delete]);
-                        }
-                        KeyCode::Home => {
-                            self.run_edit_commands(&[EditCommand::MoveToStart]);
-                        }
-                        KeyCode::End => {
-                            self.run_edit_commands(&[EditCommand::MoveToEnd]);
-                        }
-                        KeyCode::Enter => {
-                            let buffer = self.line_buffer.to_owned();
+                }
+            }
+        };
 
-                            self.run_edit_commands(&[
-                                EditCommand::AppendToHistory,
-                                EditCommand::Clear,
-                            ]);
+        // Take the list away again
+        stdout
+            .queue(MoveTo(0, prompt_row + 1))?
+            .queue(Clear(ClearType::FromCursorDown))?
+            .queue(MoveTo(0, prompt_row))?;
+        buffer_repaint(stdout, self, prompt_offset, &[], self.prompt_color)?;
 
-                            return Ok(Signal::Success(buffer));
-                        }
-                        KeyCode::Up => {
-                            self.run_edit_commands(&[EditCommand::PreviousHistory]);
-                        }
-                        KeyCode::Down => {
-                            // Down means: navigate forward through the history. If we reached the
-                            // bottom of the history, we clear the buffer, to make it feel like
-                            // zsh/bash/whatever
-                            self.run_edit_commands(&[EditCommand::NextHistory]);
-                        }
-                        KeyCode::Left => {
-                            self.run_edit_commands(&[EditCommand::MoveLeft]);
-                        }
-                        KeyCode::Right => {
-                            self.run_edit_commands(&[EditCommand::MoveRight]);
-                        }
-                        _ => {}
-                    };
-                }
-                Event::Mouse(event) => {
-                    print_message(stdout, &format!("{:?}", event))?;
-                }
-                Event::Resize(width, height) => {
-                    print_message(stdout, &format!("width: {} and height: {}", width, height))?;
+        Ok(selection)
+    }
+
+    /// Let the user know the last key was refused
+    fn signal_invalid_input(&mut self, stdout: &mut Stdout, state: &mut LineState) -> Result<()> {
+        self.input_rejected = false;
+        let feedback = match &self.constraints {
+            Some(constraints) => constraints.feedback(),
+            None => return Ok(()),
+        };
+
+        match feedback {
+            InvalidInputFeedback::Bell => {
+                stdout.queue(Print("\x07"))?;
+                stdout.flush()?;
+            }
+            InvalidInputFeedback::Flash => {
+                // `handle_wake_up` draws it in the prompt color again once this passes
+                state.flash_deadline = Some(Instant::now() + INVALID_INPUT_FLASH);
+                self.repaint(stdout, state)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Clear the line for the next `read_line` and return what it was
+    fn abandon_line(&mut self) -> String {
+        let buffer = self.line_buffer.to_string();
+        self.run_edit_commands(&[EditCommand::Clear]);
+        self.undo_stack.clear();
+        buffer
+    }
+
+    /// A handle for printing above the prompt from other threads, see `ExternalPrinter`
+    pub fn create_external_printer(&self) -> ExternalPrinter {
+        ExternalPrinter {
+            messages: self.external_messages.clone(),
+        }
+    }
+
+    /// Print the messages queued by `ExternalPrinter`s on their own lines above
+    /// the prompt. The prompt line is pushed down below them as it is, along
+    /// with whatever the application printed before the prompt.
+    fn print_external_messages(&mut self, stdout: &mut Stdout, state: &LineState) -> Result<()> {
+        let messages = match self.external_messages.lock() {
+            Ok(mut messages) => std::mem::take(&mut *messages),
+            Err(poisoned) => std::mem::take(&mut *poisoned.into_inner()),
+        };
+        if messages.is_empty() {
+            return Ok(());
+        }
+
+        let (width, _) = size()?;
+        let lines: Vec<&str> = messages
+            .iter()
+            .flat_map(|message| message.lines())
+            .collect();
+        // Long lines wrap, and take up more rows
+        let rows: usize = lines
+            .iter()
+            .map(|line| display_width(line).div_ceil(width as usize).max(1))
+            .sum();
+        let rows = rows as u16;
+
+        // Make room below the prompt first, scrolling if it is on the last row
+        stdout
+            .queue(Print("\n".repeat(rows as usize)))?
+            .queue(MoveUp(rows))?
+            .queue(MoveToColumn(1))?
+            // IL, insert blank lines at the cursor and push the rest of the screen down
+            .queue(Print(format!("\x1b[{}L", rows)))?;
+        for line in lines {
+            stdout
+                .queue(Print(caret_notation(line)))?
+                .queue(Print("\r\n"))?;
+        }
+
+        self.repaint(stdout, state)
+    }
+
+    pub fn read_line(&mut self, stdout: &mut Stdout) -> Result<Signal> {
+        self.read_line_until(stdout, None, None)
+    }
+
+    /// Like `read_line`, but give up once `deadline` passes or another thread sets
+    /// `interrupt`, returning `Signal::Timeout` or `Signal::Interrupted` with the
+    /// line typed so far. The flag is left set, clear it before reusing it.
+    pub fn read_line_until(
+        &mut self,
+        stdout: &mut Stdout,
+        deadline: Option<Instant>,
+        interrupt: Option<Arc<AtomicBool>>,
+    ) -> Result<Signal> {
+        let _raw_mode = RawModeGuard::new()?;
+
+        let mut state = self.start_line()?;
+        let signal = self.edit_line(stdout, &mut state, deadline, interrupt);
+        state.set_block_cursor(stdout, false)?;
+
+        signal
+    }
+
+    fn edit_line(
+        &mut self,
+        stdout: &mut Stdout,
+        state: &mut LineState,
+        deadline: Option<Instant>,
+        interrupt: Option<Arc<AtomicBool>>,
+    ) -> Result<Signal> {
+        self.repaint(stdout, state)?;
+
+        loop {
+            if let Some(signal) = self.give_up(deadline, interrupt.as_deref()) {
+                return Ok(signal);
+            }
+
+            let signal = if poll(state.wake_up_timeout(deadline))? {
+                self.handle_event(stdout, state, read()?)?
+            } else {
+                self.handle_wake_up(stdout, state)?
+            };
+            if let Some(signal) = signal {
+                return Ok(signal);
+            }
+        }
+    }
+
+    /// Start a line with the prompt at the cursor. Every line starts out
+    /// inserting, like in readline.
+    /// Give up on the line if `deadline` has passed or `interrupt` is set,
+    /// returning the signal for it
+    fn give_up(
+        &mut self,
+        deadline: Option<Instant>,
+        interrupt: Option<&AtomicBool>,
+    ) -> Option<Signal> {
+        if interrupt.is_some_and(|interrupt| interrupt.load(Ordering::SeqCst)) {
+            return Some(Signal::Interrupted(self.abandon_line()));
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Some(Signal::Timeout(self.abandon_line()));
+        }
+        None
+    }
+
+    /// Give up on the line in `state` from outside of reading it, along with the
+    /// chord being typed and a binding waiting for its argument
+    #[cfg(feature = "async")]
+    pub(crate) fn cancel_line(&mut self, state: &mut LineState) {
+        state.pending.clear();
+        state.chord_deadline = None;
+        state.argument = None;
+        self.abandon_line();
+    }
+
+    pub(crate) fn start_line(&mut self) -> Result<LineState> {
+        self.overwrite = false;
+        LineState::new()
+    }
+
+    /// Handle an event read while editing the line in `state`
+    pub(crate) fn handle_event(
+        &mut self,
+        stdout: &mut Stdout,
+        state: &mut LineState,
+        event: Event,
+    ) -> Result<Option<Signal>> {
+        match event {
+            // Waiting for an argument is part of the line, rather than a read of
+            // its own, so that deadlines and async reads carry on meanwhile
+            Event::Key(key) => match state.argument.take() {
+                Some(binding) => self.run_binding_with_argument(binding, key),
+                None => {
+                    if let Some(signal) = self.handle_key(&mut state.pending, key)? {
+                        return Ok(Some(signal));
+                    }
+                    self.take_binding_requests(state);
                 }
+            },
+            Event::Mouse(event) => {
+                print_message(stdout, &format!("{:?}", event))?;
+            }
+            Event::Resize(width, height) => {
+                print_message(stdout, &format!("width: {} and height: {}", width, height))?;
+            }
+        }
+        self.finish_binding(stdout, state)?;
+        // Keys arriving faster than the wake ups, like a paste, don't hold them up
+        self.print_external_messages(stdout, state)?;
+
+        Ok(None)
+    }
+
+    /// Take over what the binding that just ran left for the line: the key it
+    /// waits for as an argument, the chord still being typed, and the screen it
+    /// asked to clear or to start over on after being continued
+    fn take_binding_requests(&mut self, state: &mut LineState) {
+        state.argument = self.awaiting_argument.take();
+        // Only wait for so long if we are in the middle of a chord
+        state.chord_deadline = match state.pending.is_empty() {
+            true => None,
+            false => Some(Instant::now() + self.chord_timeout),
+        };
+        if let Some(scrollback) = self.clear_screen.take() {
+            state.clear_screen = Some(scrollback);
+        }
+        state.resumed |= std::mem::take(&mut self.resumed);
+    }
+
+    /// Bring the screen up to date after a binding ran, from a key press or a
+    /// chord that timed out alike
+    fn finish_binding(&mut self, stdout: &mut Stdout, state: &mut LineState) -> Result<()> {
+        if let Some(scrollback) = state.clear_screen.take() {
+            stdout.queue(Clear(ClearType::All))?.queue(MoveTo(0, 0))?;
+            if scrollback {
+                stdout.queue(Print(CLEAR_SCROLLBACK))?;
+            }
+            stdout.flush()?;
+            state.prompt_offset = position()?.0 + 1;
+        }
+        // The shell has written over the screen while we were stopped, so start
+        // the prompt over wherever the cursor is now
+        if std::mem::take(&mut state.resumed) {
+            state.prompt_offset = position()?.0 + 1;
+            // `terminal::suspend` put the cursor back in the terminal's own shape
+            state.block_cursor = false;
+        }
+        state.set_block_cursor(stdout, self.overwrite)?;
+        self.repaint(stdout, state)?;
+        if self.input_rejected {
+            self.signal_invalid_input(stdout, state)?;
+        }
+
+        Ok(())
+    }
+
+    /// Give up on a chord that took too long, and run the binding of the keys
+    /// typed so far instead if they have one
+    fn time_out_chord(&mut self, state: &mut LineState) -> Result<Option<Signal>> {
+        let signal = self.flush_pending(&mut state.pending)?;
+        self.take_binding_requests(state);
+
+        Ok(signal)
+    }
+
+    /// Handle waking up without an event: give up on a chord that took too long,
+    /// end a flash of the prompt, and print messages from other threads
+    pub(crate) fn handle_wake_up(
+        &mut self,
+        stdout: &mut Stdout,
+        state: &mut LineState,
+    ) -> Result<Option<Signal>> {
+        if state
+            .chord_deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            if let Some(signal) = self.time_out_chord(state)? {
+                return Ok(Some(signal));
             }
-            buffer_repaint(stdout, &self, prompt_offset)?;
+            self.finish_binding(stdout, state)?;
+        }
+        if state.end_flash() {
+            self.repaint(stdout, state)?;
+        }
+        self.print_external_messages(stdout, state)?;
+
+        Ok(None)
+    }
+
+    /// Draw the prompt and the line in `state` again
+    pub(crate) fn repaint(&self, stdout: &mut Stdout, state: &LineState) -> Result<()> {
+        let prompt_color = match state.flash_deadline {
+            Some(_) => INVALID_INPUT_COLOR,
+            None => self.prompt_color,
+        };
+        buffer_repaint(
+            stdout,
+            self,
+            state.prompt_offset,
+            &state.pending,
+            prompt_color,
+        )
+    }
+}
+
+#[test]
+fn custom_command_test() {
+    let mut engine = Engine::new();
+    engine.register_command("shout", |line_buffer| {
+        let shouted = line_buffer.to_uppercase();
+        line_buffer.set_buffer(shouted);
+    });
+    engine.register_command("split-char", |line_buffer| {
+        line_buffer.set_buffer("é!".to_string());
+        line_buffer.set_insertion_point(1);
+    });
+
+    engine.run_edit_commands(&[EditCommand::InsertChar('a'), EditCommand::MoveRight]);
+    engine.run_edit_commands(&[EditCommand::Custom("whisper".to_string())]);
+    assert_eq!(&*engine.line_buffer, "a");
+    assert_eq!(engine.get_insertion_point(), 1);
+
+    engine.run_edit_commands(&[EditCommand::Custom("shout".to_string())]);
+    assert_eq!(&*engine.line_buffer, "A");
+    assert_eq!(engine.get_insertion_point(), 1);
+
+    // Halfway through the é is no place for the cursor
+    engine.run_edit_commands(&[EditCommand::Custom("split-char".to_string())]);
+    assert_eq!(&*engine.line_buffer, "é!");
+    assert_eq!(engine.get_insertion_point(), "é!".len());
+}
+
+#[test]
+fn submit_hook_test() {
+    let mut engine = Engine::new();
+    engine.add_submit_hook(|buffer| match buffer {
+        "ls -la" => SubmitDecision::Rewrite("ls -l -a".to_string()),
+        "rm -rf /" => SubmitDecision::Reject,
+        _ => SubmitDecision::Accept,
+    });
+    let type_str = |engine: &mut Engine, s: &str| {
+        for c in s.chars() {
+            engine.run_edit_commands(&[EditCommand::InsertChar(c), EditCommand::MoveRight]);
         }
+    };
+
+    type_str(&mut engine, "ls -la");
+    assert!(matches!(
+        engine.run_binding(Binding::Submit),
+        Ok(Some(Signal::Success(line))) if line == "ls -l -a"
+    ));
+    assert_eq!(engine.history.front().map(String::as_str), Some("ls -l -a"));
+    assert_eq!(engine.history.len(), 1);
+
+    type_str(&mut engine, "rm -rf /");
+    assert!(matches!(engine.run_binding(Binding::Submit), Ok(None)));
+    assert_eq!(&*engine.line_buffer, "rm -rf /");
+    assert_eq!(engine.history.len(), 1);
+
+    assert!(matches!(
+        engine.run_binding(Binding::Host("help".to_string())),
+        Ok(Some(Signal::Host(name))) if name == "help"
+    ));
+    assert_eq!(&*engine.line_buffer, "rm -rf /");
+    assert_eq!(engine.get_insertion_point(), "rm -rf /".len());
+}
+
+#[test]
+fn undo_test() {
+    let mut engine = Engine::new();
+    let type_str = |engine: &mut Engine, s: &str| {
+        for c in s.chars() {
+            engine.run_edit_commands(&[EditCommand::InsertChar(c), EditCommand::MoveRight]);
+        }
+    };
+
+    type_str(&mut engine, "hello  world");
+    engine.run_edit_commands(&[EditCommand::Undo]);
+    assert_eq!(&*engine.line_buffer, "hello  ");
+    engine.run_edit_commands(&[EditCommand::Undo]);
+    assert_eq!(&*engine.line_buffer, "");
+
+    // Also with the key bindings, where space expands abbreviations
+    for c in "git co".chars() {
+        engine
+            .handle_key(&mut Vec::new(), KeyCode::Char(c).into())
+            .unwrap();
     }
+    engine.run_edit_commands(&[EditCommand::Undo]);
+    assert_eq!(&*engine.line_buffer, "git ");
+    engine.run_edit_commands(&[EditCommand::Undo]);
+    assert_eq!(&*engine.line_buffer, "");
+
+    // Moving in between starts a new step
+    type_str(&mut engine, "ab");
+    engine.run_edit_commands(&[EditCommand::MoveLeft]);
+    type_str(&mut engine, "c");
+    engine.run_edit_commands(&[EditCommand::Undo]);
+    assert_eq!(&*engine.line_buffer, "ab");
+
+    // Only the latest changes are kept
+    type_str(&mut engine, &" x".repeat(2 * UNDO_LIMIT));
+    assert_eq!(engine.undo_stack.len(), UNDO_LIMIT);
+}
+
+#[test]
+fn auto_pair_test() {
+    let mut engine = Engine::new();
+    engine.set_auto_pair(true);
+    let type_char = |engine: &mut Engine, c| {
+        engine.run_edit_commands(&[EditCommand::InsertChar(c), EditCommand::MoveRight])
+    };
+
+    type_char(&mut engine, '(');
+    assert_eq!(&*engine.line_buffer, "()");
+    assert_eq!(engine.get_insertion_point(), 1);
+
+    type_char(&mut engine, '"');
+    type_char(&mut engine, 'a');
+    type_char(&mut engine, '"');
+    assert_eq!(&*engine.line_buffer, "(\"a\")");
+    assert_eq!(engine.get_insertion_point(), 4);
+
+    type_char(&mut engine, '[');
+    engine.run_edit_commands(&[EditCommand::Backspace]);
+    assert_eq!(&*engine.line_buffer, "(\"a\")");
+
+    type_char(&mut engine, 'n');
+    type_char(&mut engine, '\'');
+    assert_eq!(&*engine.line_buffer, "(\"a\"n')");
+
+    type_char(&mut engine, ')');
+    assert_eq!(&*engine.line_buffer, "(\"a\"n')");
+    assert_eq!(engine.get_insertion_point(), engine.line_buffer.len());
+}
+
+#[test]
+fn password_zeroing_test() {
+    let mut engine = Engine::new();
+    engine.password_mask = Some(PasswordMask::Hidden);
+    engine.line_buffer = LineBuffer::with_capacity(PASSWORD_CAPACITY);
+    let capacity = engine.line_buffer.capacity();
+    for c in "hunter2".chars() {
+        engine.run_edit_commands(&[EditCommand::InsertChar(c), EditCommand::MoveRight]);
+    }
+    engine.run_edit_commands(&[EditCommand::Backspace]);
+
+    assert!(matches!(
+        engine.run_binding(Binding::Submit),
+        Ok(Some(Signal::Success(password))) if password == "hunter"
+    ));
+    assert!(engine.line_buffer.is_empty());
+    assert!(!engine.line_buffer.spare_capacity().is_empty());
+    assert!(engine
+        .line_buffer
+        .spare_capacity()
+        .iter()
+        .all(|&byte| byte == 0));
+
+    // The buffer is never reallocated, what doesn't fit is refused
+    for _ in 0..capacity {
+        engine.run_edit_commands(&[EditCommand::InsertChar('x'), EditCommand::MoveRight]);
+    }
+    assert!(engine.input_rejected);
+    assert!(engine.line_buffer.len() < capacity);
+    assert_eq!(engine.line_buffer.capacity(), capacity);
+}
+
+#[test]
+fn input_constraints_test() {
+    let mut engine = Engine::new();
+    engine.constraints = Some(
+        InputConstraints::new()
+            .with_max_length(4)
+            .with_char_filter(|c| c.is_ascii_hexdigit())
+            .with_pattern("^[0-9a-f]{3}$|^[0-9a-f]{4}$")
+            .unwrap(),
+    );
+    let type_str = |engine: &mut Engine, s: &str| {
+        for c in s.chars() {
+            engine.run_edit_commands(&[EditCommand::InsertChar(c), EditCommand::MoveRight]);
+        }
+    };
+
+    type_str(&mut engine, "fgx0");
+    assert_eq!(&*engine.line_buffer, "f0");
+    assert!(engine.input_rejected);
+    assert!(matches!(engine.run_binding(Binding::Submit), Ok(None)));
+
+    type_str(&mut engine, "a1b2");
+    assert_eq!(&*engine.line_buffer, "f0a1");
+    assert_eq!(engine.get_insertion_point(), 4);
+
+    // Pasting is held to the same rules as typing
+    engine.run_edit_commands(&[EditCommand::MoveLeft, EditCommand::CutToEnd]);
+    engine.run_edit_commands(&[EditCommand::InsertCutBuffer, EditCommand::InsertCutBuffer]);
+    assert_eq!(&*engine.line_buffer, "f0a1");
+
+    assert!(matches!(
+        engine.run_binding(Binding::Submit),
+        Ok(Some(Signal::Success(line))) if line == "f0a1"
+    ));
+}
+
+#[test]
+fn choice_test() {
+    let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+    let engine = Engine::new();
+    let choose = |key| engine.choose(key, &['y', 'n', 'A'], Some('n'));
+
+    let shift_y = KeyEvent::new(KeyCode::Char('Y'), KeyModifiers::SHIFT);
+    assert!(matches!(choose(shift_y), Some(Signal::Success(choice)) if choice == "y"));
+    assert!(matches!(
+        choose(KeyCode::Char('a').into()),
+        Some(Signal::Success(choice)) if choice == "A"
+    ));
+    assert!(matches!(
+        choose(KeyCode::Enter.into()),
+        Some(Signal::Success(choice)) if choice == "n"
+    ));
+    assert!(matches!(choose(ctrl('c')), Some(Signal::CtrlC)));
+
+    // Keys that aren't offered are refused, and so is Enter without a default
+    assert!(choose(KeyCode::Char('x').into()).is_none());
+    assert!(choose(ctrl('y')).is_none());
+    assert!(choose(KeyCode::Left.into()).is_none());
+    assert!(engine
+        .choose(KeyCode::Enter.into(), &['y', 'n'], None)
+        .is_none());
+}
+
+#[test]
+fn picker_test() {
+    let items = ["apple", "banana", "cherry", "date", "elderberry", "fig"];
+    let mut picker = Picker::new(items.iter().map(|item| item.to_string()).collect(), 3, true);
+
+    picker.move_highlight(4);
+    assert_eq!((picker.highlighted, picker.scroll), (4, 2));
+    picker.move_highlight(10);
+    assert_eq!((picker.highlighted, picker.scroll), (5, 3));
+    picker.move_highlight(-4);
+    assert_eq!((picker.highlighted, picker.scroll), (1, 1));
+    assert_eq!(picker.selection(), vec![1]);
+
+    picker.filter("ERR");
+    assert_eq!(picker.matches, vec![2, 4]);
+    picker.move_highlight(1);
+    picker.toggle_highlighted();
+    picker.filter("");
+    picker.toggle_highlighted();
+    assert_eq!(picker.selection(), vec![0, 4]);
+
+    picker.filter("kiwi");
+    picker.move_highlight(1);
+    picker.toggle_highlighted();
+    assert_eq!(picker.highlighted, 0);
+    assert_eq!(picker.selection(), vec![0, 4]);
+
+    // The filter is what was typed, with no emoji or overwriting
+    let mut engine = Engine::new();
+    for c in ":smile:".chars() {
+        engine.edit_picker_filter(KeyCode::Char(c).into());
+    }
+    engine.edit_picker_filter(KeyCode::Insert.into());
+    engine.edit_picker_filter(KeyCode::Left.into());
+    engine.edit_picker_filter(KeyCode::Char('s').into());
+    assert_eq!(&*engine.line_buffer, ":smiles:");
+}
+
+#[test]
+fn external_printer_test() {
+    let engine = Engine::new();
+    let printer = engine.create_external_printer();
+
+    let threads: Vec<_> = (0..4)
+        .map(|i| {
+            let printer = printer.clone();
+            std::thread::spawn(move || printer.print(format!("job {} done", i)))
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    let mut messages = engine.external_messages.lock().unwrap().clone();
+    messages.sort();
+    assert_eq!(
+        messages,
+        ["job 0 done", "job 1 done", "job 2 done", "job 3 done"]
+    );
+}
+
+#[test]
+fn char_argument_test() {
+    let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+    let mut engine = Engine::new();
+    let mut pending = Vec::new();
+    for c in "a.b.c".chars() {
+        engine
+            .handle_key(&mut pending, KeyCode::Char(c).into())
+            .unwrap();
+    }
+    engine.run_edit_commands(&[EditCommand::MoveToStart]);
+
+    // The bindings don't read a key themselves, they wait for the next event
+    assert!(engine
+        .handle_key(&mut pending, ctrl('5'))
+        .unwrap()
+        .is_none());
+    let binding = engine.awaiting_argument.take().unwrap();
+    engine.run_binding_with_argument(binding, KeyCode::Char('.').into());
+    assert_eq!(engine.get_insertion_point(), 1);
+
+    assert!(engine
+        .handle_key(&mut pending, ctrl('v'))
+        .unwrap()
+        .is_none());
+    let binding = engine.awaiting_argument.take().unwrap();
+    engine.run_binding_with_argument(binding, KeyCode::Tab.into());
+    assert_eq!(&*engine.line_buffer, "a\t.b.c");
+}
+
+#[test]
+fn give_up_test() {
+    let mut engine = Engine::new();
+    let interrupt = AtomicBool::new(false);
+    let later = Instant::now() + Duration::from_secs(60);
+    engine.run_edit_commands(&[EditCommand::InsertChar('a'), EditCommand::MoveRight]);
+    assert!(engine.give_up(Some(later), Some(&interrupt)).is_none());
+
+    // Giving up leaves an empty line behind and returns what was typed so far
+    assert!(matches!(
+        engine.give_up(Some(Instant::now()), Some(&interrupt)),
+        Some(Signal::Timeout(line)) if line == "a"
+    ));
+    assert!(engine.line_buffer.is_empty());
+    engine.run_edit_commands(&[EditCommand::InsertChar('b'), EditCommand::MoveRight]);
+    interrupt.store(true, Ordering::SeqCst);
+    assert!(matches!(
+        engine.give_up(Some(later), Some(&interrupt)),
+        Some(Signal::Interrupted(line)) if line == "b"
+    ));
+
+    // Waking up in time for whichever comes first, the deadline or the end of a flash
+    let mut state = LineState::at_column(0);
+    state.flash_deadline = Some(Instant::now() + Duration::from_millis(10));
+    assert!(state.wake_up_timeout(Some(later)) <= Duration::from_millis(10));
+    assert_eq!(state.wake_up_timeout(Some(Instant::now())), Duration::ZERO);
+    state.flash_deadline = Some(later);
+    assert!(!state.end_flash());
+    state.flash_deadline = Some(Instant::now());
+    assert!(state.end_flash());
+    assert_eq!(state.flash_deadline, None);
+}
+
+#[test]
+fn chord_timeout_test() {
+    let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+    let mut engine = Engine::new();
+    engine.keybindings.add_binding(
+        &[ctrl('l'), ctrl('k')],
+        Binding::Edit(vec![EditCommand::Clear]),
+    );
+    let mut state = LineState::at_column(0);
+
+    // Ctrl-L is bound on its own too, so it waits for the rest of the chord
+    assert!(engine
+        .handle_key(&mut state.pending, ctrl('l'))
+        .unwrap()
+        .is_none());
+    engine.take_binding_requests(&mut state);
+    assert!(state.chord_deadline.is_some());
+    assert_eq!(state.clear_screen, None);
+
+    // Running out of time clears the screen, the same as a lone Ctrl-L would
+    assert!(engine.time_out_chord(&mut state).unwrap().is_none());
+    assert!(state.pending.is_empty());
+    assert_eq!(state.chord_deadline, None);
+    assert_eq!(state.clear_screen, Some(false));
+}
+
+#[test]
+fn quoted_insert_test() {
+    let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+    assert_eq!(verbatim_char(KeyCode::Tab.into()), Some('\t'));
+    assert_eq!(verbatim_char(KeyCode::Esc.into()), Some('\x1b'));
+    assert_eq!(verbatim_char(ctrl('a')), Some('\x01'));
+    assert_eq!(verbatim_char(ctrl('5')), Some('\x1d'));
+    assert_eq!(verbatim_char(KeyCode::Char('x').into()), Some('x'));
+    assert_eq!(verbatim_char(KeyCode::Left.into()), None);
+
+    assert_eq!(caret_notation("%s\t%d\x1b[0m\x7f"), "%s^I%d^[[0m^?");
+    assert!(matches!(caret_notation("plain"), Cow::Borrowed("plain")));
+}
+
+#[test]
+fn overwrite_test() {
+    let mut engine = Engine::new();
+    let type_str = |engine: &mut Engine, s: &str| {
+        for c in s.chars() {
+            engine.run_edit_commands(&[EditCommand::InsertChar(c), EditCommand::MoveRight]);
+        }
+    };
+
+    type_str(&mut engine, "cafe\u{301} au lait");
+    engine.run_edit_commands(&[EditCommand::MoveToStart, EditCommand::ToggleOverwrite]);
+    type_str(&mut engine, "thé!! ");
+    assert_eq!(&*engine.line_buffer, "thé!! u lait");
+    assert_eq!(engine.get_insertion_point(), "thé!! ".len());
+
+    engine.run_edit_commands(&[EditCommand::MoveToEnd]);
+    type_str(&mut engine, "s");
+    assert_eq!(&*engine.line_buffer, "thé!! u laits");
+
+    // Only what was typed over since the cursor last moved comes back
+    engine.run_edit_commands(&[EditCommand::Backspace]);
+    engine.run_edit_commands(&[EditCommand::Backspace]);
+    assert_eq!(&*engine.line_buffer, "thé!! u lai");
+
+    engine.run_edit_commands(&[EditCommand::MoveToStart, EditCommand::MoveRight]);
+    type_str(&mut engine, "ab");
+    assert_eq!(&*engine.line_buffer, "tab!! u lai");
+    engine.run_edit_commands(&[EditCommand::Backspace]);
+    engine.run_edit_commands(&[EditCommand::Backspace]);
+    assert_eq!(&*engine.line_buffer, "thé!! u lai");
+    assert_eq!(engine.get_insertion_point(), 1);
+
+    engine.run_edit_commands(&[EditCommand::ToggleOverwrite]);
+    type_str(&mut engine, "x");
+    assert_eq!(&*engine.line_buffer, "txhé!! u lai");
 }
@@ -0,0 +1,52 @@
+use std::{fmt, io};
+
+/// Everything that can go wrong in red_line
+#[derive(Debug)]
+pub enum Error {
+    /// Reading keys from or drawing to the terminal failed
+    Terminal(io::Error),
+    /// A config or inputrc file could not be read or parsed
+    Config(String),
+    /// stdin or stdout is not a terminal, so there is no line to edit
+    NotATty,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Whether the application can carry on, maybe without its config. After
+    /// any other error, the terminal can't be used for editing.
+    pub fn is_recoverable(&self) -> bool {
+        match self {
+            Error::Terminal(err) => err.kind() == io::ErrorKind::Interrupted,
+            Error::Config(_) => true,
+            Error::NotATty => false,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Terminal(err) => write!(f, "terminal error: {}", err),
+            Error::Config(message) => write!(f, "{}", message),
+            Error::NotATty => write!(f, "not a terminal"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Terminal(err) => Some(err),
+            Error::Config(_) | Error::NotATty => None,
+        }
+    }
+}
+
+// crossterm reports all of its errors as plain io errors
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Terminal(err)
+    }
+}
@@ -0,0 +1,578 @@
+use std::{
+    fs,
+    iter::Peekable,
+    path::{Path, PathBuf},
+    str::Chars,
+    time::Duration,
+};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::engine::EditCommand;
+use crate::error::Error;
+use crate::keybindings::{Binding, CharArgument, Keybindings};
+use crate::line_buffer::WordDefinition;
+
+const ESC: char = '\x1b';
+
+/// Settings and keybindings read from a GNU readline `inputrc` file.
+///
+/// Bindings are layered on top of the default emacs bindings, and anything
+/// red_line can't honour is reported in `warnings` rather than failing the load.
+pub struct Inputrc {
+    pub keybindings: Keybindings,
+    pub completion_ignore_case: bool,
+    pub keyseq_timeout: Option<Duration>,
+    pub warnings: Vec<String>,
+}
+
+/// State of one `$if` block
+struct Conditional {
+    parent_active: bool,
+    condition: bool,
+}
+
+impl Inputrc {
+    /// Read `$INPUTRC`, falling back to `~/.inputrc`. Returns `None` if neither exists.
+    pub fn load_default(application: &str) -> Result<Option<Inputrc>, Error> {
+        let path = std::env::var_os("INPUTRC")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".inputrc")));
+
+        match path {
+            Some(path) if path.exists() => Inputrc::load(&path, application).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Read an inputrc file. `application` is matched by `$if` lines like `$if Bash`.
+    pub fn load(path: &Path, application: &str) -> Result<Inputrc, Error> {
+        let mut inputrc = Inputrc::empty();
+        inputrc
+            .read_file(path, application)
+            .map_err(|err| Error::Config(format!("{}: {}", path.display(), err)))?;
+        Ok(inputrc)
+    }
+
+    pub fn parse(contents: &str, application: &str) -> Inputrc {
+        let mut inputrc = Inputrc::empty();
+        inputrc.parse_lines(contents, application, "inputrc");
+        inputrc
+    }
+
+    fn empty() -> Inputrc {
+        Inputrc {
+            keybindings: Keybindings::emacs(),
+            completion_ignore_case: false,
+            keyseq_timeout: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    fn read_file(&mut self, path: &Path, application: &str) -> std::io::Result<()> {
+        let contents = fs::read_to_string(path)?;
+        self.parse_lines(&contents, application, &path.display().to_string());
+        Ok(())
+    }
+
+    fn parse_lines(&mut self, contents: &str, application: &str, source: &str) {
+        let mut conditionals: Vec<Conditional> = Vec::new();
+
+        for (line_number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            let location = format!("{}:{}", source, line_number + 1);
+            let active = conditionals
+                .last()
+                .is_none_or(|c| c.parent_active && c.condition);
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(directive) = line.strip_prefix('$') {
+                let (name, argument) = split_word(directive);
+                match name {
+                    "if" => conditionals.push(Conditional {
+                        parent_active: active,
+                        condition: self.evaluate_condition(argument, application),
+                    }),
+                    "else" => match conditionals.last_mut() {
+                        Some(conditional) => conditional.condition = !conditional.condition,
+                        None => self.warn(&location, "`$else` without `$if`"),
+                    },
+                    "endif" => {
+                        if conditionals.pop().is_none() {
+                            self.warn(&location, "`$endif` without `$if`")
+                        }
+                    }
+                    "include" if active => {
+                        let path = expand_home(argument);
+                        if let Err(err) = self.read_file(&path, application) {
+                            self.warn(
+                                &location,
+                                &format!("can't include {}: {}", path.display(), err),
+                            );
+                        }
+                    }
+                    "include" => {}
+                    _ => self.warn(&location, &format!("unknown directive `${}`", name)),
+                }
+                continue;
+            }
+
+            if !active {
+                continue;
+            }
+
+            if let Some(setting) = line.strip_prefix("set ") {
+                let (variable, value) = split_word(setting.trim());
+                self.set_variable(&location, variable, value);
+            } else if let Err(message) = self.parse_binding(line) {
+                self.warn(&location, &message);
+            }
+        }
+
+        if !conditionals.is_empty() {
+            self.warn(source, "missing `$endif`");
+        }
+    }
+
+    fn evaluate_condition(&self, condition: &str, application: &str) -> bool {
+        if let Some(mode) = condition.strip_prefix("mode=") {
+            // Only the emacs editing mode exists
+            mode == "emacs"
+        } else if let Some(term) = condition.strip_prefix("term=") {
+            std::env::var("TERM")
+                .is_ok_and(|current| current == term || current.split('-').next() == Some(term))
+        } else {
+            condition.eq_ignore_ascii_case(application)
+        }
+    }
+
+    fn set_variable(&mut self, location: &str, variable: &str, value: &str) {
+        let on = value.eq_ignore_ascii_case("on") || value.is_empty() || value == "1";
+        match variable.to_lowercase().as_str() {
+            "editing-mode" if value != "emacs" => self.warn(
+                location,
+                &format!("editing-mode {} is not supported, using emacs", value),
+            ),
+            "completion-ignore-case" => self.completion_ignore_case = on,
+            "keyseq-timeout" => match value.parse() {
+                Ok(millis) => self.keyseq_timeout = Some(Duration::from_millis(millis)),
+                Err(_) => self.warn(location, &format!("invalid keyseq-timeout `{}`", value)),
+            },
+            // The remaining variables tune readline features red_line doesn't have
+            _ => {}
+        }
+    }
+
+    fn parse_binding(&mut self, line: &str) -> Result<(), String> {
+        let (keys, rest) = if let Some(quoted) = line.strip_prefix('"') {
+            let end = closing_quote(quoted).ok_or("unterminated key sequence")?;
+            let rest = quoted[end + 1..]
+                .trim_start()
+                .strip_prefix(':')
+                .ok_or("missing `:` after key sequence")?;
+            (unescape(&quoted[..end])?, rest)
+        } else {
+            let colon = line.find(':').ok_or("expected a key binding")?;
+            (parse_key_name(line[..colon].trim())?, &line[colon + 1..])
+        };
+
+        let rest = rest.trim();
+        let binding = if let Some(quoted) = rest.strip_prefix('"') {
+            let end = closing_quote(quoted).ok_or("unterminated macro")?;
+            let commands = unescape(&quoted[..end])?
+                .chars()
+                .flat_map(|c| vec![EditCommand::InsertChar(c), EditCommand::MoveRight])
+                .collect();
+            Binding::Edit(commands)
+        } else {
+            let (function, _) = split_word(rest);
+            binding_for_function(function)
+                .ok_or_else(|| format!("unsupported function `{}`", function))?
+        };
+
+        let sequence = key_events(&keys);
+        // `\eb` arrives as Alt-b when typed quickly and as Esc, b otherwise
+        let esc_sequence: Vec<KeyEvent> = sequence
+            .iter()
+            .flat_map(|key| {
+                if key.modifiers.contains(KeyModifiers::ALT) {
+                    vec![
+                        KeyCode::Esc.into(),
+                        KeyEvent::new(key.code, key.modifiers - KeyModifiers::ALT),
+                    ]
+                } else {
+                    vec![*key]
+                }
+            })
+            .collect();
+        if esc_sequence != sequence {
+            self.keybindings.add_binding(&esc_sequence, binding.clone());
+        }
+        self.keybindings.add_binding(&sequence, binding);
+
+        Ok(())
+    }
+
+    fn warn(&mut self, location: &str, message: &str) {
+        self.warnings.push(format!("{}: {}", location, message));
+    }
+}
+
+/// The readline functions red_line has an equivalent for
+fn binding_for_function(function: &str) -> Option<Binding> {
+    let edit = |command| Some(Binding::Edit(vec![command]));
+
+    match function {
+        "beginning-of-line" => edit(EditCommand::MoveToStart),
+        "end-of-line" => edit(EditCommand::MoveToEnd),
+        "forward-char" => edit(EditCommand::MoveRight),
+        "backward-char" => edit(EditCommand::MoveLeft),
+        "forward-word" => edit(EditCommand::MoveWordRight(WordDefinition::Unicode)),
+        "backward-word" => edit(EditCommand::MoveWordLeft(WordDefinition::Unicode)),
+        "shell-forward-word" => edit(EditCommand::MoveWordRight(WordDefinition::BigWord)),
+        "shell-backward-word" => edit(EditCommand::MoveWordLeft(WordDefinition::BigWord)),
+        "accept-line" => Some(Binding::Submit),
+        "previous-history" => edit(EditCommand::PreviousHistory),
+        "next-history" => edit(EditCommand::NextHistory),
+        "delete-char" => Some(Binding::DeleteOrEndOfFile),
+        "backward-delete-char" => edit(EditCommand::Backspace),
+        "kill-line" => edit(EditCommand::CutToEnd),
+        "backward-kill-line" | "unix-line-discard" => edit(EditCommand::CutFromStart),
+        "kill-word" => edit(EditCommand::CutWordRight(WordDefinition::Unicode)),
+        "backward-kill-word" => edit(EditCommand::CutWordLeft(WordDefinition::Unicode)),
+        "shell-kill-word" => edit(EditCommand::CutWordRight(WordDefinition::BigWord)),
+        "shell-backward-kill-word" | "unix-word-rubout" => {
+            edit(EditCommand::CutWordLeft(WordDefinition::BigWord))
+        }
+        "unix-filename-rubout" => edit(EditCommand::CutWordLeft(WordDefinition::PathSegment)),
+        "yank" => edit(EditCommand::InsertCutBuffer),
+        "upcase-word" => edit(EditCommand::UppercaseWord),
+        "downcase-word" => edit(EditCommand::LowercaseWord),
+        "capitalize-word" => edit(EditCommand::CapitalizeChar),
+        "transpose-words" => edit(EditCommand::SwapWords),
+        "transpose-chars" => edit(EditCommand::SwapGraphemes),
+        "undo" => edit(EditCommand::Undo),
+        "quoted-insert" => Some(Binding::QuotedInsert),
+        "overwrite-mode" => edit(EditCommand::ToggleOverwrite),
+        "clear-screen" => Some(Binding::ClearScreen { scrollback: false }),
+        "clear-display" => Some(Binding::ClearScreen { scrollback: true }),
+        "character-search" => Some(Binding::ReadChar(CharArgument::MoveToCharRight)),
+        "character-search-backward" => Some(Binding::ReadChar(CharArgument::MoveToCharLeft)),
+        _ => None,
+    }
+}
+
+fn split_word(s: &str) -> (&str, &str) {
+    match s.find(char::is_whitespace) {
+        Some(i) => (&s[..i], s[i..].trim()),
+        None => (s, ""),
+    }
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => Path::new(&home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Index of the first unescaped `"` in `s`
+fn closing_quote(s: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            '"' if !escaped => return Some(i),
+            '\\' => escaped = !escaped,
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+fn control(c: char) -> char {
+    if c == '?' {
+        '\x7f'
+    } else {
+        ((c.to_ascii_lowercase() as u8) & 0x1f) as char
+    }
+}
+
+/// Turn the escapes of a quoted key sequence or macro into the raw characters
+/// a terminal would send, e.g. `\C-x\M-f` into `^X ESC f`
+fn unescape(s: &str) -> Result<String, String> {
+    let mut result = String::new();
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        let escape = chars.next().ok_or("trailing `\\`")?;
+        match escape {
+            'C' | 'M' if chars.peek() == Some(&'-') => {
+                chars.next();
+                result.push_str(&unescape_modified(escape, &mut chars)?);
+            }
+            'e' => result.push(ESC),
+            'a' => result.push('\x07'),
+            'b' => result.push('\x08'),
+            'd' => result.push('\x7f'),
+            'f' => result.push('\x0c'),
+            'n' => result.push('\n'),
+            'r' => result.push('\r'),
+            't' => result.push('\t'),
+            'v' => result.push('\x0b'),
+            'x' => {
+                let mut value = 0;
+                while let Some(digit) = chars.peek().and_then(|c| c.to_digit(16)) {
+                    value = value * 16 + digit;
+                    chars.next();
+                }
+                result.push(std::char::from_u32(value).ok_or("invalid `\\x` escape")?);
+            }
+            '0'..='7' => {
+                let mut value = escape.to_digit(8).unwrap_or(0);
+                for _ in 0..2 {
+                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                        Some(digit) => {
+                            value = value * 8 + digit;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                result.push(std::char::from_u32(value).ok_or("invalid octal escape")?);
+            }
+            other => result.push(other),
+        }
+    }
+
+    Ok(result)
+}
+
+/// The key after a `\C-` or `\M-` escape with the modifier applied. The key can
+/// have modifiers of its own, so `\M-\C-h` and `\C-\M-h` are both `ESC ^H`.
+fn unescape_modified(modifier: char, chars: &mut Peekable<Chars<'_>>) -> Result<String, String> {
+    let mut key = match chars.next() {
+        Some('\\') => match chars.next() {
+            Some('e') => ESC.to_string(),
+            Some(inner @ ('C' | 'M')) if chars.peek() == Some(&'-') => {
+                chars.next();
+                unescape_modified(inner, chars)?
+            }
+            Some(c) => c.to_string(),
+            None => return Err("trailing `\\`".to_string()),
+        },
+        Some(c) => c.to_string(),
+        None => return Err(format!("missing key after `\\{}-`", modifier)),
+    };
+    if modifier == 'C' {
+        // After the ESC of a Meta, if there is one
+        if let Some(c) = key.pop() {
+            key.push(control(c));
+        }
+    } else {
+        key.insert(0, ESC);
+    }
+
+    Ok(key)
+}
+
+/// Parse the `Control-u` / `Meta-Rubout` style of naming a single key
+fn parse_key_name(name: &str) -> Result<String, String> {
+    let mut rest = name;
+    let mut ctrl = false;
+    let mut meta = false;
+
+    loop {
+        let lower = rest.to_lowercase();
+        if lower.starts_with("control-") {
+            ctrl = true;
+            rest = &rest["control-".len()..];
+        } else if lower.starts_with("c-") {
+            ctrl = true;
+            rest = &rest["c-".len()..];
+        } else if lower.starts_with("meta-") {
+            meta = true;
+            rest = &rest["meta-".len()..];
+        } else if lower.starts_with("m-") {
+            meta = true;
+            rest = &rest["m-".len()..];
+        } else {
+            break;
+        }
+    }
+
+    let key = match rest.to_lowercase().as_str() {
+        "del" | "rubout" => '\x7f',
+        "esc" | "escape" => ESC,
+        "lfd" | "newline" => '\n',
+        "ret" | "return" => '\r',
+        "space" | "spc" => ' ',
+        "tab" => '\t',
+        _ => {
+            let mut chars = rest.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => c,
+                _ => return Err(format!("unknown key name `{}`", name)),
+            }
+        }
+    };
+
+    let mut keys = String::new();
+    if meta {
+        keys.push(ESC);
+    }
+    keys.push(if ctrl { control(key) } else { key });
+    Ok(keys)
+}
+
+/// Decode raw terminal input into key events, the same way crossterm does
+fn key_events(keys: &str) -> Vec<KeyEvent> {
+    let chars: Vec<char> = keys.chars().collect();
+    let mut events = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        if chars[i] == ESC && i + 1 < chars.len() {
+            if let Some((event, len)) = escape_sequence(&chars[i + 1..]) {
+                events.push(event);
+                i += len + 1;
+                continue;
+            }
+            let mut event = char_event(chars[i + 1]);
+            event.modifiers |= KeyModifiers::ALT;
+            events.push(event);
+            i += 2;
+        } else {
+            events.push(char_event(chars[i]));
+            i += 1;
+        }
+    }
+
+    events
+}
+
+fn char_event(c: char) -> KeyEvent {
+    match c {
+        ESC => KeyCode::Esc.into(),
+        '\t' => KeyCode::Tab.into(),
+        '\r' | '\n' => KeyCode::Enter.into(),
+        '\x7f' => KeyCode::Backspace.into(),
+        '\0' => KeyEvent::new(KeyCode::Char(' '), KeyModifiers::CONTROL),
+        '\x01'..='\x1a' => KeyEvent::new(
+            KeyCode::Char((c as u8 - 0x1 + b'a') as char),
+            KeyModifiers::CONTROL,
+        ),
+        '\x1c'..='\x1f' => KeyEvent::new(
+            KeyCode::Char((c as u8 - 0x1c + b'4') as char),
+            KeyModifiers::CONTROL,
+        ),
+        c if c.is_uppercase() => KeyEvent::new(KeyCode::Char(c), KeyModifiers::SHIFT),
+        c => KeyCode::Char(c).into(),
+    }
+}
+
+/// The common cursor and editing key sequences, after the leading escape
+fn escape_sequence(chars: &[char]) -> Option<(KeyEvent, usize)> {
+    const SEQUENCES: &[(&str, KeyCode, KeyModifiers)] = &[
+        ("[1;5C", KeyCode::Right, KeyModifiers::CONTROL),
+        ("[1;5D", KeyCode::Left, KeyModifiers::CONTROL),
+        ("[1;3C", KeyCode::Right, KeyModifiers::ALT),
+        ("[1;3D", KeyCode::Left, KeyModifiers::ALT),
+        ("[1~", KeyCode::Home, KeyModifiers::NONE),
+        ("[2~", KeyCode::Insert, KeyModifiers::NONE),
+        ("[3~", KeyCode::Delete, KeyModifiers::NONE),
+        ("[4~", KeyCode::End, KeyModifiers::NONE),
+        ("[5~", KeyCode::PageUp, KeyModifiers::NONE),
+        ("[6~", KeyCode::PageDown, KeyModifiers::NONE),
+        ("[A", KeyCode::Up, KeyModifiers::NONE),
+        ("[B", KeyCode::Down, KeyModifiers::NONE),
+        ("[C", KeyCode::Right, KeyModifiers::NONE),
+        ("[D", KeyCode::Left, KeyModifiers::NONE),
+        ("[H", KeyCode::Home, KeyModifiers::NONE),
+        ("[F", KeyCode::End, KeyModifiers::NONE),
+        ("OA", KeyCode::Up, KeyModifiers::NONE),
+        ("OB", KeyCode::Down, KeyModifiers::NONE),
+        ("OC", KeyCode::Right, KeyModifiers::NONE),
+        ("OD", KeyCode::Left, KeyModifiers::NONE),
+        ("OH", KeyCode::Home, KeyModifiers::NONE),
+        ("OF", KeyCode::End, KeyModifiers::NONE),
+    ];
+
+    SEQUENCES.iter().find_map(|(sequence, code, modifiers)| {
+        let len = sequence.chars().count();
+        if chars.len() >= len && chars[..len].iter().copied().eq(sequence.chars()) {
+            Some((KeyEvent::new(*code, *modifiers), len))
+        } else {
+            None
+        }
+    })
+}
+
+#[test]
+fn inputrc_test() {
+    use crate::keybindings::KeySequenceMatch;
+
+    let inputrc = Inputrc::parse(
+        r#"
+# comment
+set editing-mode vi
+set completion-ignore-case on
+set keyseq-timeout 250
+"\C-x\C-e": edit-and-execute-command
+"\C-xg": "git status"
+"\e[1;5D": backward-word
+"\M-\C-h": backward-kill-word
+"\C-\M-b": backward-word
+$if mode=emacs
+Meta-Rubout: unix-filename-rubout
+$else
+"\C-a": end-of-line
+$endif
+$if Bash
+"\C-a": end-of-line
+$endif
+"#,
+        "red_line",
+    );
+
+    assert!(inputrc.completion_ignore_case);
+    assert_eq!(inputrc.keyseq_timeout, Some(Duration::from_millis(250)));
+    assert_eq!(inputrc.warnings.len(), 2);
+    assert!(inputrc.warnings[1].contains("edit-and-execute-command"));
+
+    let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+    let find = |sequence: &[KeyEvent]| match inputrc.keybindings.find_binding(sequence) {
+        KeySequenceMatch::Exact(Binding::Edit(commands)) => Some(commands.len()),
+        _ => None,
+    };
+    assert_eq!(find(&[ctrl('x'), KeyCode::Char('g').into()]), Some(20));
+    assert!(matches!(
+        inputrc.keybindings.find_binding(&[ctrl('a')]),
+        KeySequenceMatch::Exact(Binding::Edit(commands))
+            if matches!(commands[..], [EditCommand::MoveToStart])
+    ));
+    assert_eq!(
+        find(&[KeyEvent::new(KeyCode::Left, KeyModifiers::CONTROL)]),
+        Some(1)
+    );
+    assert_eq!(
+        find(&[KeyEvent::new(KeyCode::Backspace, KeyModifiers::ALT)]),
+        Some(1)
+    );
+    assert_eq!(
+        find(&[KeyCode::Esc.into(), KeyCode::Backspace.into()]),
+        Some(1)
+    );
+    let ctrl_alt = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL | KeyModifiers::ALT);
+    assert_eq!(find(&[ctrl_alt('h')]), Some(1));
+    assert_eq!(find(&[ctrl_alt('b')]), Some(1));
+    assert_eq!(unescape(r"\M-\C-h").unwrap(), "\x1b\x08");
+    assert_eq!(unescape(r"\C-\M-h").unwrap(), "\x1b\x08");
+}
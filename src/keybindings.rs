@@ -0,0 +1,492 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::engine::EditCommand;
+use crate::line_buffer::{CharSearch, WordDefinition};
+
+/// A command that takes the character typed right after its key binding as argument
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum CharArgument {
+    MoveToCharRight,
+    MoveToCharLeft,
+    CutToCharRight,
+}
+
+impl CharArgument {
+    pub fn edit_command(self, c: char) -> EditCommand {
+        match self {
+            CharArgument::MoveToCharRight => EditCommand::MoveToChar(CharSearch::ToRight(c)),
+            CharArgument::MoveToCharLeft => EditCommand::MoveToChar(CharSearch::ToLeft(c)),
+            CharArgument::CutToCharRight => EditCommand::CutToChar(CharSearch::ToRight(c)),
+        }
+    }
+}
+
+/// What a key sequence is bound to
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Binding {
+    /// Run the edit commands on the buffer
+    Edit(Vec<EditCommand>),
+    /// Read one more character and run the command with it
+    ReadChar(CharArgument),
+    /// Insert the next key as it is, control characters included
+    QuotedInsert,
+    /// Accept the line, `Signal::Success`
+    Submit,
+    /// Abandon the line, `Signal::CtrlC`
+    Interrupt,
+    /// Delete the grapheme under the cursor, or `Signal::CtrlD` on an empty line
+    DeleteOrEndOfFile,
+    /// End `read_line` with `Signal::Host(name)`, leaving the buffer as it is
+    Host(String),
+    /// Stop the process like Ctrl-Z in a shell, and repaint once it is continued
+    Suspend,
+    /// Clear the screen, and the scrollback too if asked, and draw the prompt
+    /// again at the top
+    ClearScreen { scrollback: bool },
+}
+
+/// The result of looking up a key sequence
+pub enum KeySequenceMatch<'a> {
+    /// The sequence is bound and no longer sequence starts with it
+    Exact(&'a Binding),
+    /// The sequence is the start of a longer binding, and may be bound itself
+    Prefix(Option<&'a Binding>),
+    /// Nothing is bound to the sequence or starts with it
+    NoMatch,
+}
+
+#[derive(Clone, Default)]
+struct KeyTrie {
+    binding: Option<Binding>,
+    children: HashMap<KeyEvent, KeyTrie>,
+}
+
+impl KeyTrie {
+    fn collect_bindings<'a>(
+        &'a self,
+        sequence: &mut Vec<KeyEvent>,
+        bindings: &mut Vec<(Vec<KeyEvent>, &'a Binding)>,
+    ) {
+        if let Some(binding) = &self.binding {
+            bindings.push((sequence.clone(), binding));
+        }
+        for (key, child) in &self.children {
+            sequence.push(*key);
+            child.collect_bindings(sequence, bindings);
+            sequence.pop();
+        }
+    }
+}
+
+/// The key sequences the engine reacts to. Every sequence is a trie path,
+/// so that multi-key chords like `C-x C-u` can share their prefix.
+///
+/// Serialized as a map from key sequences like `"C-x C-u"` to their binding.
+#[derive(Clone, Default)]
+pub struct Keybindings {
+    root: KeyTrie,
+}
+
+impl Serialize for Keybindings {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.bindings()
+            .into_iter()
+            .map(|(sequence, binding)| (key_sequence_to_string(&sequence), binding))
+            .collect::<BTreeMap<_, _>>()
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Keybindings {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut keybindings = Keybindings::new();
+        for (keys, binding) in BTreeMap::<String, Binding>::deserialize(deserializer)? {
+            let sequence = parse_key_sequence(&keys).map_err(D::Error::custom)?;
+            keybindings.add_binding(&sequence, binding);
+        }
+        Ok(keybindings)
+    }
+}
+
+impl Keybindings {
+    /// An empty set of keybindings, only plain characters are inserted
+    pub fn new() -> Keybindings {
+        Keybindings::default()
+    }
+
+    /// Bind `binding` to `sequence`, replacing any earlier binding of it
+    pub fn add_binding(&mut self, sequence: &[KeyEvent], binding: Binding) {
+        let node = sequence.iter().fold(&mut self.root, |node, key| {
+            node.children.entry(normalize_key(*key)).or_default()
+        });
+        node.binding = Some(binding);
+    }
+
+    /// Bind an `M-` key both as Alt chord and as the `ESC key` sequence,
+    /// for terminals (and fingers) that send the escape separately
+    pub fn add_meta_binding(&mut self, code: KeyCode, modifiers: KeyModifiers, binding: Binding) {
+        self.add_binding(
+            &[KeyEvent::new(code, modifiers | KeyModifiers::ALT)],
+            binding.clone(),
+        );
+        self.add_binding(
+            &[KeyCode::Esc.into(), KeyEvent::new(code, modifiers)],
+            binding,
+        );
+    }
+
+    /// Add all of `other`'s bindings, replacing ours where they overlap
+    pub fn extend(&mut self, other: &Keybindings) {
+        for (sequence, binding) in other.bindings() {
+            self.add_binding(&sequence, binding.clone());
+        }
+    }
+
+    /// Every bound key sequence along with its binding
+    pub fn bindings(&self) -> Vec<(Vec<KeyEvent>, &Binding)> {
+        let mut bindings = Vec::new();
+        self.root.collect_bindings(&mut Vec::new(), &mut bindings);
+        bindings
+    }
+
+    pub fn find_binding(&self, sequence: &[KeyEvent]) -> KeySequenceMatch<'_> {
+        let mut node = &self.root;
+        for key in sequence {
+            match node.children.get(&normalize_key(*key)) {
+                Some(child) => node = child,
+                None => return KeySequenceMatch::NoMatch,
+            }
+        }
+
+        if !node.children.is_empty() {
+            return KeySequenceMatch::Prefix(node.binding.as_ref());
+        }
+        match &node.binding {
+            Some(binding) => KeySequenceMatch::Exact(binding),
+            None => KeySequenceMatch::NoMatch,
+        }
+    }
+
+    /// The default emacs style bindings
+    pub fn emacs() -> Keybindings {
+        let mut keybindings = Keybindings::new();
+
+        let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+        let edit = |command| Binding::Edit(vec![command]);
+
+        keybindings.add_binding(&[ctrl('d')], Binding::DeleteOrEndOfFile);
+        keybindings.add_binding(&[ctrl('a')], edit(EditCommand::MoveToStart));
+        keybindings.add_binding(&[ctrl('e')], edit(EditCommand::MoveToEnd));
+        keybindings.add_binding(&[ctrl('k')], edit(EditCommand::CutToEnd));
+        keybindings.add_binding(&[ctrl('u')], edit(EditCommand::CutFromStart));
+        keybindings.add_binding(&[ctrl('y')], edit(EditCommand::InsertCutBuffer));
+        keybindings.add_binding(&[ctrl('b')], edit(EditCommand::MoveLeft));
+        keybindings.add_binding(&[ctrl('f')], edit(EditCommand::MoveRight));
+        keybindings.add_binding(&[ctrl('c')], Binding::Interrupt);
+        keybindings.add_binding(&[ctrl('z')], Binding::Suspend);
+        keybindings.add_binding(&[ctrl('h')], edit(EditCommand::Backspace));
+        keybindings.add_binding(&[ctrl('l')], Binding::ClearScreen { scrollback: false });
+        keybindings.add_binding(&[ctrl('v')], Binding::QuotedInsert);
+        // Like bash's unix-word-rubout, only whitespace separates words here
+        keybindings.add_binding(
+            &[ctrl('w')],
+            edit(EditCommand::CutWordLeft(WordDefinition::BigWord)),
+        );
+        keybindings.add_binding(
+            &[KeyEvent::new(KeyCode::Left, KeyModifiers::CONTROL)],
+            edit(EditCommand::MoveWordLeft(WordDefinition::Unicode)),
+        );
+        keybindings.add_binding(
+            &[KeyEvent::new(KeyCode::Right, KeyModifiers::CONTROL)],
+            edit(EditCommand::MoveWordRight(WordDefinition::Unicode)),
+        );
+        keybindings.add_binding(&[ctrl('p')], edit(EditCommand::PreviousHistory));
+        keybindings.add_binding(&[ctrl('n')], edit(EditCommand::NextHistory));
+        keybindings.add_binding(&[ctrl('t')], edit(EditCommand::SwapGraphemes));
+        // Terminals report Ctrl-] as Ctrl-5
+        for c in [']', '5'] {
+            keybindings.add_binding(&[ctrl(c)], Binding::ReadChar(CharArgument::MoveToCharRight));
+            keybindings.add_meta_binding(
+                KeyCode::Char(c),
+                KeyModifiers::CONTROL,
+                Binding::ReadChar(CharArgument::MoveToCharLeft),
+            );
+        }
+        keybindings.add_binding(
+            &[ctrl('x'), KeyCode::Backspace.into()],
+            edit(EditCommand::CutFromStart),
+        );
+        // C-_ arrives as C-7
+        keybindings.add_binding(&[ctrl('_')], edit(EditCommand::Undo));
+        keybindings.add_binding(&[ctrl('7')], edit(EditCommand::Undo));
+        keybindings.add_binding(&[ctrl('x'), ctrl('u')], edit(EditCommand::Undo));
+
+        // Space expands abbreviations, C-SPC inserts a space as it is
+        keybindings.add_binding(
+            &[KeyCode::Char(' ').into()],
+            Binding::Edit(vec![
+                EditCommand::ExpandAbbreviation,
+                EditCommand::InsertChar(' '),
+                EditCommand::MoveRight,
+            ]),
+        );
+        keybindings.add_binding(
+            &[ctrl(' ')],
+            Binding::Edit(vec![EditCommand::InsertChar(' '), EditCommand::MoveRight]),
+        );
+
+        // Emoji by name, `:smile:` or `:smi` TAB, and characters by code point, `U+1F60A` C-x 8
+        keybindings.add_binding(
+            &[KeyCode::Char(':').into()],
+            Binding::Edit(vec![
+                EditCommand::InsertChar(':'),
+                EditCommand::MoveRight,
+                EditCommand::ExpandShortcode,
+            ]),
+        );
+        keybindings.add_binding(&[KeyCode::Tab.into()], edit(EditCommand::CompleteShortcode));
+        keybindings.add_binding(
+            &[ctrl('x'), KeyCode::Char('8').into()],
+            edit(EditCommand::ExpandCodePoint),
+        );
+
+        let meta_bindings = [
+            (
+                KeyCode::Char('b'),
+                edit(EditCommand::MoveWordLeft(WordDefinition::Unicode)),
+            ),
+            (
+                KeyCode::Char('f'),
+                edit(EditCommand::MoveWordRight(WordDefinition::Unicode)),
+            ),
+            (
+                KeyCode::Char('d'),
+                edit(EditCommand::CutWordRight(WordDefinition::Unicode)),
+            ),
+            (
+                KeyCode::Left,
+                edit(EditCommand::MoveWordLeft(WordDefinition::Unicode)),
+            ),
+            (
+                KeyCode::Right,
+                edit(EditCommand::MoveWordRight(WordDefinition::Unicode)),
+            ),
+            (KeyCode::Char('u'), edit(EditCommand::UppercaseWord)),
+            (KeyCode::Char('l'), edit(EditCommand::LowercaseWord)),
+            (KeyCode::Char('c'), edit(EditCommand::CapitalizeChar)),
+            (KeyCode::Char('t'), edit(EditCommand::SwapWords)),
+            (
+                KeyCode::Char('z'),
+                Binding::ReadChar(CharArgument::CutToCharRight),
+            ),
+            (KeyCode::Char(';'), edit(EditCommand::RepeatCharSearch)),
+            (KeyCode::Char(','), edit(EditCommand::ReverseCharSearch)),
+            (
+                KeyCode::Backspace,
+                edit(EditCommand::CutWordLeft(WordDefinition::PathSegment)),
+            ),
+        ];
+        for (code, binding) in meta_bindings {
+            keybindings.add_meta_binding(code, KeyModifiers::NONE, binding);
+        }
+
+        let subword_bindings = [
+            (
+                KeyCode::Left,
+                edit(EditCommand::MoveWordLeft(WordDefinition::SubWord)),
+            ),
+            (
+                KeyCode::Right,
+                edit(EditCommand::MoveWordRight(WordDefinition::SubWord)),
+            ),
+            (
+                KeyCode::Char('h'),
+                edit(EditCommand::CutWordLeft(WordDefinition::SubWord)),
+            ),
+        ];
+        for (code, binding) in subword_bindings {
+            keybindings.add_meta_binding(code, KeyModifiers::CONTROL, binding);
+        }
+
+        keybindings.add_binding(&[KeyCode::Backspace.into()], edit(EditCommand::Backspace));
+        keybindings.add_binding(&[KeyCode::Delete.into()], edit(EditCommand::Delete));
+        keybindings.add_binding(
+            &[KeyCode::Insert.into()],
+            edit(EditCommand::ToggleOverwrite),
+        );
+        keybindings.add_binding(&[KeyCode::Home.into()], edit(EditCommand::MoveToStart));
+        keybindings.add_binding(&[KeyCode::End.into()], edit(EditCommand::MoveToEnd));
+        keybindings.add_binding(&[KeyCode::Enter.into()], Binding::Submit);
+        keybindings.add_binding(&[KeyCode::Up.into()], edit(EditCommand::PreviousHistory));
+        // Down means: navigate forward through the history. If we reached the
+        // bottom of the history, we clear the buffer, to make it feel like
+        // zsh/bash/whatever
+        keybindings.add_binding(&[KeyCode::Down.into()], edit(EditCommand::NextHistory));
+        keybindings.add_binding(&[KeyCode::Left.into()], edit(EditCommand::MoveLeft));
+        keybindings.add_binding(&[KeyCode::Right.into()], edit(EditCommand::MoveRight));
+
+        keybindings
+    }
+}
+
+/// Shift only matters for characters, where it is already part of the char
+/// itself, so `S-<left>` is looked up as `<left>`
+fn normalize_key(key: KeyEvent) -> KeyEvent {
+    match key.code {
+        KeyCode::Char(_) => key,
+        _ => KeyEvent::new(key.code, key.modifiers - KeyModifiers::SHIFT),
+    }
+}
+
+/// Format a key sequence the way emacs echoes a pending chord, e.g. `C-x M-f`
+pub fn key_sequence_to_string(sequence: &[KeyEvent]) -> String {
+    sequence
+        .iter()
+        .map(|key| {
+            let mut name = String::new();
+            if key.modifiers.contains(KeyModifiers::CONTROL) {
+                name.push_str("C-");
+            }
+            if key.modifiers.contains(KeyModifiers::ALT) {
+                name.push_str("M-");
+            }
+            match key.code {
+                KeyCode::Char(' ') => name.push_str("SPC"),
+                KeyCode::Char(c) => name.push(c),
+                KeyCode::Esc => name.push_str("ESC"),
+                KeyCode::Enter => name.push_str("RET"),
+                KeyCode::Tab => name.push_str("TAB"),
+                KeyCode::Backspace => name.push_str("DEL"),
+                KeyCode::F(n) => name.push_str(&format!("<f{}>", n)),
+                code => name.push_str(&format!("<{:?}>", code).to_lowercase()),
+            }
+            name
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Parse the notation of `key_sequence_to_string` back into key events
+pub fn parse_key_sequence(s: &str) -> Result<Vec<KeyEvent>, String> {
+    s.split_whitespace()
+        .map(|name| {
+            let mut rest = name;
+            let mut modifiers = KeyModifiers::NONE;
+            loop {
+                let modifier = match rest.get(..2) {
+                    Some("C-") => KeyModifiers::CONTROL,
+                    Some("M-") => KeyModifiers::ALT,
+                    Some("S-") => KeyModifiers::SHIFT,
+                    _ => break,
+                };
+                if rest.len() == 2 {
+                    break;
+                }
+                modifiers |= modifier;
+                rest = &rest[2..];
+            }
+
+            let code = match rest {
+                "SPC" => KeyCode::Char(' '),
+                "ESC" => KeyCode::Esc,
+                "RET" => KeyCode::Enter,
+                "TAB" => KeyCode::Tab,
+                "DEL" => KeyCode::Backspace,
+                "<left>" => KeyCode::Left,
+                "<right>" => KeyCode::Right,
+                "<up>" => KeyCode::Up,
+                "<down>" => KeyCode::Down,
+                "<home>" => KeyCode::Home,
+                "<end>" => KeyCode::End,
+                "<pageup>" => KeyCode::PageUp,
+                "<pagedown>" => KeyCode::PageDown,
+                "<backtab>" => KeyCode::BackTab,
+                "<delete>" => KeyCode::Delete,
+                "<insert>" => KeyCode::Insert,
+                "<null>" => KeyCode::Null,
+                _ => {
+                    let mut chars = rest.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) => KeyCode::Char(c),
+                        _ => rest
+                            .strip_prefix("<f")
+                            .and_then(|n| n.strip_suffix('>'))
+                            .and_then(|n| n.parse().ok())
+                            .map(KeyCode::F)
+                            .ok_or_else(|| format!("unknown key `{}`", name))?,
+                    }
+                }
+            };
+
+            Ok(KeyEvent::new(code, modifiers))
+        })
+        .collect()
+}
+
+#[test]
+fn key_sequence_test() {
+    let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+    let keybindings = Keybindings::emacs();
+
+    assert!(matches!(
+        keybindings.find_binding(&[ctrl('x')]),
+        KeySequenceMatch::Prefix(None)
+    ));
+    assert!(matches!(
+        keybindings.find_binding(&[ctrl('x'), KeyCode::Backspace.into()]),
+        KeySequenceMatch::Exact(Binding::Edit(_))
+    ));
+    assert!(matches!(
+        keybindings.find_binding(&[ctrl('x'), ctrl('q')]),
+        KeySequenceMatch::NoMatch
+    ));
+    assert!(matches!(
+        keybindings.find_binding(&[KeyCode::Esc.into(), KeyCode::Char('b').into()]),
+        KeySequenceMatch::Exact(Binding::Edit(_))
+    ));
+    assert!(matches!(
+        keybindings.find_binding(&[KeyEvent::new(KeyCode::Left, KeyModifiers::SHIFT)]),
+        KeySequenceMatch::Exact(Binding::Edit(_))
+    ));
+    assert_eq!(
+        key_sequence_to_string(&[
+            ctrl('x'),
+            KeyEvent::new(KeyCode::Char('f'), KeyModifiers::ALT)
+        ]),
+        "C-x M-f"
+    );
+}
+
+#[test]
+fn keybindings_serde_test() {
+    let keybindings = Keybindings::emacs();
+
+    let toml = toml::to_string(&keybindings).unwrap();
+    let from_toml: Keybindings = toml::from_str(&toml).unwrap();
+    let json = serde_json::to_string(&keybindings).unwrap();
+    let from_json: Keybindings = serde_json::from_str(&json).unwrap();
+
+    let mut expected = keybindings.bindings();
+    expected.sort_by_key(|(sequence, _)| key_sequence_to_string(sequence));
+    for round_tripped in [from_toml, from_json] {
+        let mut bindings = round_tripped.bindings();
+        bindings.sort_by_key(|(sequence, _)| key_sequence_to_string(sequence));
+        assert_eq!(bindings, expected);
+    }
+
+    assert_eq!(
+        parse_key_sequence("C-M-] <f5> S-<left>").unwrap(),
+        vec![
+            KeyEvent::new(
+                KeyCode::Char(']'),
+                KeyModifiers::CONTROL | KeyModifiers::ALT
+            ),
+            KeyEvent::new(KeyCode::F(5), KeyModifiers::NONE),
+            KeyEvent::new(KeyCode::Left, KeyModifiers::SHIFT),
+        ]
+    );
+    assert!(parse_key_sequence("C-<nope>").is_err());
+}
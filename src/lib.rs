@@ -0,0 +1,28 @@
+#[cfg(feature = "async")]
+mod async_read;
+mod config;
+mod constraints;
+mod diagnostic;
+mod emoji;
+mod engine;
+mod error;
+mod inputrc;
+mod keybindings;
+mod line_buffer;
+mod terminal;
+
+pub use config::Config;
+pub use constraints::{CharFilter, InputConstraints, InvalidInputFeedback};
+pub use diagnostic::print_events;
+pub use engine::{
+    print_crlf, print_message, CustomCommand, EditCommand, Engine, ExternalPrinter, PasswordMask,
+    Selection, Signal, SubmitDecision, SubmitHook,
+};
+pub use error::{Error, Result};
+pub use inputrc::Inputrc;
+pub use keybindings::{
+    key_sequence_to_string, parse_key_sequence, Binding, CharArgument, KeySequenceMatch,
+    Keybindings,
+};
+pub use line_buffer::{Brackets, CharSearch, LineBuffer, WordDefinition};
+pub use terminal::RawModeGuard;
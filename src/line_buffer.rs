@@ -1,5 +1,81 @@
-use std::ops::Deref;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ops::{Deref, Range};
 use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::emoji::{emoji, emoji_names};
+
+/// A search for a single grapheme on the line, in the spirit of emacs'
+/// `character-search` and vi's `f`/`F`/`t`/`T`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum CharSearch {
+    /// Onto the next occurrence to the right (vi `f`)
+    ToRight(char),
+    /// Up to, but not onto, the next occurrence to the right (vi `t`)
+    TillRight(char),
+    /// Onto the previous occurrence to the left (vi `F`)
+    ToLeft(char),
+    /// Up to, but not onto, the previous occurrence to the left (vi `T`)
+    TillLeft(char),
+}
+
+impl CharSearch {
+    /// The same search in the opposite direction, used by vi's `,`
+    pub fn reversed(self) -> CharSearch {
+        match self {
+            CharSearch::ToRight(c) => CharSearch::ToLeft(c),
+            CharSearch::TillRight(c) => CharSearch::TillLeft(c),
+            CharSearch::ToLeft(c) => CharSearch::ToRight(c),
+            CharSearch::TillLeft(c) => CharSearch::TillRight(c),
+        }
+    }
+}
+
+/// The different notions of a "word" used by word motions and cuts
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum WordDefinition {
+    /// Runs of alphanumeric characters, split on Unicode word boundaries
+    Unicode,
+    /// Anything between whitespace, like bash's `unix-word-rubout`
+    BigWord,
+    /// Anything between whitespace or `/`, like bash's `unix-filename-rubout`
+    PathSegment,
+    /// The parts of `camelCase` and `snake_case` identifiers
+    SubWord,
+}
+
+impl WordDefinition {
+    /// The words of `s` along with their byte offsets
+    fn word_indices(self, s: &str) -> Vec<(usize, &str)> {
+        match self {
+            WordDefinition::Unicode => s
+                .split_word_bound_indices()
+                .filter(|(_, word)| !is_word_boundary(word))
+                .collect(),
+            WordDefinition::BigWord => split_on(s, char::is_whitespace),
+            WordDefinition::PathSegment => split_on(s, |c| c.is_whitespace() || c == '/'),
+            WordDefinition::SubWord => s
+                .split_word_bound_indices()
+                .filter(|(_, word)| !is_word_boundary(word))
+                .flat_map(|(offset, word)| {
+                    split_subwords(word)
+                        .into_iter()
+                        .map(move |(i, subword)| (offset + i, subword))
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Where the brackets of a line pair up, see `LineBuffer::brackets`
+#[derive(Debug, Default, PartialEq)]
+pub struct Brackets {
+    /// Indices of each opening bracket and its closing counterpart
+    pub pairs: Vec<(usize, usize)>,
+    /// Indices of brackets without a counterpart
+    pub unmatched: Vec<usize>,
+}
 
 pub struct LineBuffer {
     buffer: String,
@@ -14,6 +90,12 @@ impl Deref for LineBuffer {
     }
 }
 
+impl Default for LineBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl LineBuffer {
     pub fn new() -> LineBuffer {
         LineBuffer {
@@ -22,6 +104,19 @@ impl LineBuffer {
         }
     }
 
+    /// An empty buffer with room for at least `capacity` bytes before it has to
+    /// move to a bigger allocation
+    pub fn with_capacity(capacity: usize) -> LineBuffer {
+        LineBuffer {
+            buffer: String::with_capacity(capacity),
+            insertion_point: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buffer.capacity()
+    }
+
     pub fn set_insertion_point(&mut self, pos: usize) {
         self.insertion_point = pos;
     }
@@ -34,6 +129,21 @@ impl LineBuffer {
         self.buffer = buffer;
     }
 
+    /// Set the insertion point, moving it back to the start of the grapheme
+    /// `pos` falls into, or to the end if it is past the buffer
+    pub fn set_insertion_point_clamped(&mut self, pos: usize) {
+        self.insertion_point = if pos >= self.buffer.len() {
+            self.buffer.len()
+        } else {
+            self.buffer
+                .grapheme_indices(true)
+                .map(|(i, _)| i)
+                .take_while(|&i| i <= pos)
+                .last()
+                .unwrap_or(0)
+        };
+    }
+
     pub fn move_to_end(&mut self) -> usize {
         self.insertion_point = self.buffer.len();
 
@@ -51,27 +161,114 @@ impl LineBuffer {
     pub fn grapheme_left_index(&self) -> usize {
         self.buffer[..self.insertion_point]
             .grapheme_indices(true)
-            .last()
+            .next_back()
             .map(|(i, _)| i)
             .unwrap_or(0)
     }
 
-    pub fn word_right_index(&self) -> usize {
-        self.buffer[self.insertion_point..]
-            .split_word_bound_indices()
-            .find(|(_, word)| !is_word_boundary(word))
-            .map(|(i, word)| self.insertion_point + i + word.len())
-            .unwrap_or_else(|| self.buffer.len())
+    /// End of the word the insertion point is in, or of the next one. Words are
+    /// found in the whole buffer, as cutting `HTTPRequest` at the insertion point
+    /// would change where its subwords split.
+    pub fn word_right_index(&self, word_definition: WordDefinition) -> usize {
+        word_definition
+            .word_indices(&self.buffer)
+            .into_iter()
+            .map(|(i, word)| i + word.len())
+            .find(|&end| end > self.insertion_point)
+            .unwrap_or(self.buffer.len())
     }
 
-    pub fn word_left_index(&self) -> usize {
-        self.buffer[..self.insertion_point]
-            .split_word_bound_indices()
-            .filter(|(_, word)| !is_word_boundary(word))
-            .last()
+    /// Start of the word the insertion point is in, or of the previous one
+    pub fn word_left_index(&self, word_definition: WordDefinition) -> usize {
+        word_definition
+            .word_indices(&self.buffer)
+            .into_iter()
             .map(|(i, _)| i)
+            .take_while(|&start| start < self.insertion_point)
+            .last()
             .unwrap_or(0)
     }
+
+    /// Index of the next occurrence of `c` right of the grapheme under the insertion point
+    pub fn find_char_right_index(&self, c: char) -> Option<usize> {
+        self.find_char_right_from(self.insertion_point, c)
+    }
+
+    fn find_char_right_from(&self, from: usize, c: char) -> Option<usize> {
+        let needle = c.to_string();
+        self.buffer[from..]
+            .grapheme_indices(true)
+            .skip(1)
+            .find(|(_, grapheme)| *grapheme == needle)
+            .map(|(i, _)| from + i)
+    }
+
+    /// Index of the previous occurrence of `c` left of the insertion point
+    pub fn find_char_left_index(&self, c: char) -> Option<usize> {
+        self.find_char_left_from(self.insertion_point, c)
+    }
+
+    fn find_char_left_from(&self, from: usize, c: char) -> Option<usize> {
+        let needle = c.to_string();
+        self.buffer[..from]
+            .grapheme_indices(true)
+            .rev()
+            .find(|(_, grapheme)| *grapheme == needle)
+            .map(|(i, _)| i)
+    }
+
+    /// Where the insertion point lands after running `search`
+    pub fn char_search_index(&self, search: CharSearch) -> Option<usize> {
+        self.char_search_index_from(self.insertion_point, search)
+    }
+
+    /// Where the insertion point lands after running `search` again, as vi's `;`
+    /// does. Searches up to a character start one grapheme further along, so
+    /// that they don't get stuck right next to the character they found before.
+    pub fn repeated_char_search_index(&self, search: CharSearch) -> Option<usize> {
+        let from = match search {
+            CharSearch::TillRight(_) => self.grapheme_right_index(),
+            CharSearch::TillLeft(_) => self.grapheme_left_index(),
+            CharSearch::ToRight(_) | CharSearch::ToLeft(_) => self.insertion_point,
+        };
+        self.char_search_index_from(from, search)
+    }
+
+    fn char_search_index_from(&self, from: usize, search: CharSearch) -> Option<usize> {
+        match search {
+            CharSearch::ToRight(c) => self.find_char_right_from(from, c),
+            CharSearch::TillRight(c) => self.find_char_right_from(from, c).map(|found| {
+                self.buffer[..found]
+                    .grapheme_indices(true)
+                    .next_back()
+                    .map(|(i, _)| i.max(from))
+                    .unwrap_or(from)
+            }),
+            CharSearch::ToLeft(c) => self.find_char_left_from(from, c),
+            CharSearch::TillLeft(c) => self
+                .find_char_left_from(from, c)
+                .map(|found| found + c.len_utf8()),
+        }
+    }
+
+    /// The range a cut using `search` removes. Searches onto a character include it.
+    pub fn char_search_cut_range(&self, search: CharSearch) -> Option<Range<usize>> {
+        match search {
+            CharSearch::ToRight(c) => self
+                .find_char_right_index(c)
+                .map(|found| self.insertion_point..found + c.len_utf8()),
+            CharSearch::TillRight(c) => self
+                .find_char_right_index(c)
+                .map(|found| self.insertion_point..found),
+            CharSearch::ToLeft(c) => self
+                .find_char_left_index(c)
+                .map(|found| found..self.insertion_point),
+            CharSearch::TillLeft(c) => self
+                .find_char_left_index(c)
+                .map(|found| found + c.len_utf8()..self.insertion_point),
+        }
+    }
+
     pub fn move_right(&mut self) {
         self.insertion_point = self.grapheme_right_index();
     }
@@ -80,13 +277,13 @@ impl LineBuffer {
         self.insertion_point = self.grapheme_left_index();
     }
 
-    pub fn move_word_left(&mut self) -> usize {
-        self.insertion_point = self.word_left_index();
+    pub fn move_word_left(&mut self, word_definition: WordDefinition) -> usize {
+        self.insertion_point = self.word_left_index(word_definition);
         self.insertion_point
     }
 
-    pub fn move_word_right(&mut self) -> usize {
-        self.insertion_point = self.word_right_index();
+    pub fn move_word_right(&mut self, word_definition: WordDefinition) -> usize {
+        self.insertion_point = self.word_right_index(word_definition);
         self.insertion_point
     }
 
@@ -103,6 +300,22 @@ impl LineBuffer {
         self.insertion_point = 0;
     }
 
+    /// Like `clear`, but overwrite the contents with zeroes first, for secrets
+    pub fn clear_and_zero(&mut self) {
+        zero_string(&mut self.buffer);
+        self.insertion_point = 0;
+    }
+
+    /// What is allocated past the end of the buffer, once it has all been written
+    #[cfg(test)]
+    pub(crate) fn spare_capacity(&self) -> &[u8] {
+        let len = self.buffer.len();
+        // Only sound after `clear_and_zero`, which writes the whole allocation
+        unsafe {
+            std::slice::from_raw_parts(self.buffer.as_ptr().add(len), self.buffer.capacity() - len)
+        }
+    }
+
     pub fn clear_to_end(&mut self) {
         self.buffer.truncate(self.insertion_point);
     }
@@ -126,6 +339,198 @@ impl LineBuffer {
         self.buffer.replace_range(range, replace_with);
     }
 
+    /// Replace the word ending at the insertion point with its expansion, if it is an
+    /// abbreviation. Returns whether anything was expanded.
+    pub fn expand_abbreviation(&mut self, abbreviations: &HashMap<String, String>) -> bool {
+        let at_word_end = self.buffer[self.insertion_point..]
+            .chars()
+            .next()
+            .is_none_or(char::is_whitespace);
+        let word_start = self.word_left_index(WordDefinition::BigWord);
+        let word = &self.buffer[word_start..self.insertion_point];
+        if !at_word_end || word.is_empty() || word.ends_with(char::is_whitespace) {
+            return false;
+        }
+
+        match abbreviations.get(word) {
+            Some(expansion) => {
+                let expansion = expansion.clone();
+                self.replace_range(word_start..self.insertion_point, &expansion);
+                self.insertion_point = word_start + expansion.len();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replace a code point like `U+1F60A` right before the insertion point with
+    /// the character itself. Returns whether anything was replaced.
+    pub fn expand_code_point(&mut self) -> bool {
+        let before = &self.buffer[..self.insertion_point];
+        let hex_start = before
+            .trim_end_matches(|c: char| c.is_ascii_hexdigit())
+            .len();
+        let hex = &before[hex_start..];
+        let start = match before[..hex_start]
+            .strip_suffix("U+")
+            .or_else(|| before[..hex_start].strip_suffix("u+"))
+        {
+            Some(rest) if (1..=6).contains(&hex.len()) => rest.len(),
+            _ => return false,
+        };
+
+        match u32::from_str_radix(hex, 16).ok().and_then(char::from_u32) {
+            Some(c) => {
+                self.replace_before_insertion_point(start, &c.to_string());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replace a `:shortcode:` right before the insertion point with its emoji.
+    /// Returns whether anything was replaced.
+    pub fn expand_shortcode(&mut self) -> bool {
+        let end = match self.buffer[..self.insertion_point].strip_suffix(':') {
+            Some(before) => before.len(),
+            None => return false,
+        };
+        let (colon, emoji) = match self.shortcode_before(end) {
+            Some((colon, name)) => match emoji(name) {
+                Some(emoji) => (colon, emoji),
+                None => return false,
+            },
+            None => return false,
+        };
+
+        self.replace_before_insertion_point(colon, emoji);
+        true
+    }
+
+    /// Complete a `:shortcode` right before the insertion point as far as the
+    /// emoji names starting with it agree, and replace it with its emoji once
+    /// it names one. With `ignore_case`, `:Smi` completes like `:smi`.
+    /// Returns whether anything changed.
+    pub fn complete_shortcode(&mut self, ignore_case: bool) -> bool {
+        let (colon, typed) = match self.shortcode_before(self.insertion_point) {
+            Some(shortcode) => shortcode,
+            None => return false,
+        };
+        // The names are all lowercase
+        let prefix = match ignore_case {
+            true => typed.to_ascii_lowercase(),
+            false => typed.to_string(),
+        };
+        let names: Vec<&str> = emoji_names(&prefix).collect();
+        let common = match names.split_first() {
+            Some((first, rest)) => rest.iter().fold(*first, |common, name| {
+                let len = common
+                    .bytes()
+                    .zip(name.bytes())
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                &common[..len]
+            }),
+            None => return false,
+        };
+
+        if names.len() == 1 || common == prefix {
+            match emoji(common) {
+                Some(emoji) => self.replace_before_insertion_point(colon, emoji),
+                None => return false,
+            }
+        } else {
+            // Also puts what was typed in the case of the names
+            self.replace_before_insertion_point(colon, &format!(":{}", common));
+        }
+        true
+    }
+
+    /// Where the colon of a `:name` ending at `end` is, and the name. The colon
+    /// has to start a word, so `a:b` is not a shortcode.
+    fn shortcode_before(&self, end: usize) -> Option<(usize, &str)> {
+        let colon = self.buffer[..end].rfind(':')?;
+        let name = &self.buffer[colon + 1..end];
+        let starts_word = self.buffer[..colon]
+            .chars()
+            .next_back()
+            .is_none_or(char::is_whitespace);
+        let is_name = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '+' | '-'));
+
+        if starts_word && is_name {
+            Some((colon, name))
+        } else {
+            None
+        }
+    }
+
+    /// Replace everything from `start` up to the insertion point with `text`,
+    /// and move the insertion point after it
+    fn replace_before_insertion_point(&mut self, start: usize, text: &str) {
+        self.clear_range(start..self.insertion_point);
+        self.insert_str(start, text);
+        self.insertion_point = start + text.len();
+    }
+
+    /// Pair up the `()`, `[]` and `{}` of the buffer. Brackets inside quotes don't count.
+    pub fn brackets(&self) -> Brackets {
+        let mut brackets = Brackets::default();
+        let mut open: Vec<(usize, char)> = Vec::new();
+        let mut quote = None;
+        let mut escaped = false;
+
+        for (i, c) in self.buffer.char_indices() {
+            if escaped {
+                escaped = false;
+                continue;
+            }
+            match (quote, c) {
+                (Some('\''), '\'') => quote = None,
+                (Some('\''), _) => {}
+                (_, '\\') => escaped = true,
+                (Some('"'), '"') => quote = None,
+                (Some(_), _) => {}
+                (None, '"') | (None, '\'') => quote = Some(c),
+                (None, '(') | (None, '[') | (None, '{') => open.push((i, c)),
+                (None, ')') | (None, ']') | (None, '}') => {
+                    let opener = match c {
+                        ')' => '(',
+                        ']' => '[',
+                        _ => '{',
+                    };
+                    match open.last() {
+                        Some(&(start, last)) if last == opener => {
+                            open.pop();
+                            brackets.pairs.push((start, i));
+                        }
+                        _ => brackets.unmatched.push(i),
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        brackets.unmatched.extend(open.into_iter().map(|(i, _)| i));
+        brackets.unmatched.sort_unstable();
+        brackets
+    }
+
+    /// The bracket pair the insertion point is on, or right after
+    pub fn matching_brackets(&self, brackets: &Brackets) -> Option<(usize, usize)> {
+        let on = self.insertion_point;
+        let after = self.grapheme_left_index();
+        [on, after].iter().find_map(|&index| {
+            brackets
+                .pairs
+                .iter()
+                .copied()
+                .find(|&(open, close)| open == index || close == index)
+        })
+    }
+
     pub fn on_whitespace(&self) -> bool {
         self.buffer[self.get_insertion_point()..]
             .chars()
@@ -135,14 +540,297 @@ impl LineBuffer {
     }
 }
 
+/// Overwrite the whole allocation of `s` in place with zeroes and leave it empty.
+/// Past the length there may be bytes left behind by backspace or cuts.
+pub(crate) fn zero_string(s: &mut String) {
+    s.clear();
+    let capacity = s.capacity();
+    let start = s.as_mut_ptr();
+    for i in 0..capacity {
+        // Volatile, so the writes aren't left out for memory that is about to be freed.
+        // Safety: within the allocation, and zeroes keep the empty string valid UTF-8.
+        unsafe { std::ptr::write_volatile(start.add(i), 0) };
+    }
+}
+
+/// How many columns `s` takes up on screen, with control characters drawn in
+/// caret notation like `^I`
+pub(crate) fn display_width(s: &str) -> usize {
+    s.graphemes(true).map(grapheme_width).sum()
+}
+
+/// The longest start of `s` that fits in `max` columns, see `display_width`
+pub(crate) fn truncate_to_width(s: &str, max: usize) -> &str {
+    let mut width = 0;
+    for (index, grapheme) in s.grapheme_indices(true) {
+        width += grapheme_width(grapheme);
+        if width > max {
+            return &s[..index];
+        }
+    }
+    s
+}
+
+fn grapheme_width(grapheme: &str) -> usize {
+    // Control characters are graphemes of their own, apart from CR LF
+    match grapheme.chars().filter(char::is_ascii_control).count() {
+        0 => grapheme.width(),
+        controls => 2 * controls,
+    }
+}
+
 /// Match any sequence of characters that are considered a word boundary
 fn is_word_boundary(s: &str) -> bool {
     !s.chars().any(char::is_alphanumeric)
 }
 
+/// Split `s` into the runs of characters between separators
+fn split_on(s: &str, is_separator: impl Fn(char) -> bool) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut word_start = None;
+
+    for (i, c) in s.char_indices() {
+        if is_separator(c) {
+            if let Some(start) = word_start.take() {
+                words.push((start, &s[start..i]));
+            }
+        } else if word_start.is_none() {
+            word_start = Some(i);
+        }
+    }
+    if let Some(start) = word_start {
+        words.push((start, &s[start..]));
+    }
+
+    words
+}
+
+/// Split a single word at underscores and case changes, so that
+/// `parseHTTPRequest_v2` becomes `parse`, `HTTP`, `Request` and `v2`
+fn split_subwords(word: &str) -> Vec<(usize, &str)> {
+    let chars: Vec<(usize, char)> = word.char_indices().collect();
+    let mut subwords = Vec::new();
+    let mut start = None;
+
+    for (n, &(i, c)) in chars.iter().enumerate() {
+        if c == '_' {
+            if let Some(start) = start.take() {
+                subwords.push((start, &word[start..i]));
+            }
+            continue;
+        }
+
+        if let Some(subword_start) = start {
+            let prev = chars[n - 1].1;
+            let next_is_lower = chars
+                .get(n + 1)
+                .is_some_and(|(_, next)| next.is_lowercase());
+            let camel_hump = !prev.is_uppercase() && c.is_uppercase();
+            let acronym_end = prev.is_uppercase() && c.is_uppercase() && next_is_lower;
+            if camel_hump || acronym_end {
+                subwords.push((subword_start, &word[subword_start..i]));
+                start = Some(i);
+            }
+        } else {
+            start = Some(i);
+        }
+    }
+    if let Some(start) = start {
+        subwords.push((start, &word[start..]));
+    }
+
+    subwords
+}
+
 #[test]
+#[allow(clippy::no_effect)]
 fn emoji_test() {
     //TODO
     "😊";
     "🤦🏼‍♂️";
 }
+
+#[test]
+fn char_search_test() {
+    let mut line_buffer = LineBuffer::new();
+    line_buffer.set_buffer("a.b.c".to_string());
+
+    assert_eq!(
+        line_buffer.char_search_index(CharSearch::ToRight('.')),
+        Some(1)
+    );
+    assert_eq!(
+        line_buffer.char_search_index(CharSearch::TillRight('c')),
+        Some(3)
+    );
+    assert_eq!(
+        line_buffer.char_search_index(CharSearch::ToRight('x')),
+        None
+    );
+    assert_eq!(
+        line_buffer.char_search_cut_range(CharSearch::ToRight('b')),
+        Some(0..3)
+    );
+
+    line_buffer.move_to_end();
+    assert_eq!(
+        line_buffer.char_search_index(CharSearch::ToLeft('.')),
+        Some(3)
+    );
+    assert_eq!(
+        line_buffer.char_search_index(CharSearch::TillLeft('.')),
+        Some(4)
+    );
+    assert_eq!(
+        line_buffer.char_search_cut_range(CharSearch::TillLeft('a')),
+        Some(1..5)
+    );
+
+    // Repeating a search up to a character doesn't get stuck right next to it
+    line_buffer.set_insertion_point(0);
+    assert_eq!(
+        line_buffer.char_search_index(CharSearch::TillRight('.')),
+        Some(0)
+    );
+    assert_eq!(
+        line_buffer.repeated_char_search_index(CharSearch::TillRight('.')),
+        Some(2)
+    );
+    line_buffer.set_insertion_point(4);
+    assert_eq!(
+        line_buffer.repeated_char_search_index(CharSearch::TillLeft('.')),
+        Some(2)
+    );
+}
+
+#[test]
+fn word_definition_test() {
+    let mut line_buffer = LineBuffer::new();
+    line_buffer.set_buffer("git checkout feature/foo-bar".to_string());
+    line_buffer.move_to_end();
+
+    assert_eq!(line_buffer.word_left_index(WordDefinition::Unicode), 25);
+    assert_eq!(line_buffer.word_left_index(WordDefinition::PathSegment), 21);
+    assert_eq!(line_buffer.word_left_index(WordDefinition::BigWord), 13);
+
+    line_buffer.set_buffer("parseHTTPRequest_v2".to_string());
+    line_buffer.set_insertion_point(0);
+    assert_eq!(line_buffer.move_word_right(WordDefinition::SubWord), 5);
+    assert_eq!(line_buffer.move_word_right(WordDefinition::SubWord), 9);
+    assert_eq!(line_buffer.move_word_right(WordDefinition::SubWord), 16);
+    assert_eq!(line_buffer.move_word_right(WordDefinition::SubWord), 19);
+    assert_eq!(line_buffer.move_word_left(WordDefinition::SubWord), 17);
+    assert_eq!(line_buffer.move_word_left(WordDefinition::Unicode), 0);
+
+    // An acronym followed by a word isn't split differently from inside it
+    line_buffer.set_insertion_point(10);
+    assert_eq!(line_buffer.word_left_index(WordDefinition::SubWord), 9);
+    line_buffer.set_insertion_point(7);
+    assert_eq!(line_buffer.word_right_index(WordDefinition::SubWord), 9);
+    assert_eq!(line_buffer.word_left_index(WordDefinition::SubWord), 5);
+}
+
+#[test]
+fn abbreviation_test() {
+    let abbreviations: HashMap<String, String> =
+        vec![("gco".to_string(), "git checkout".to_string())]
+            .into_iter()
+            .collect();
+    let mut line_buffer = LineBuffer::new();
+
+    line_buffer.set_buffer("sudo gco".to_string());
+    line_buffer.move_to_end();
+    assert!(line_buffer.expand_abbreviation(&abbreviations));
+    assert_eq!(&*line_buffer, "sudo git checkout");
+    assert_eq!(line_buffer.get_insertion_point(), "sudo git checkout".len());
+
+    // Only whole words expand
+    line_buffer.set_buffer("gcom".to_string());
+    line_buffer.set_insertion_point(3);
+    assert!(!line_buffer.expand_abbreviation(&abbreviations));
+    line_buffer.set_buffer("xgco".to_string());
+    line_buffer.move_to_end();
+    assert!(!line_buffer.expand_abbreviation(&abbreviations));
+}
+
+#[test]
+fn brackets_test() {
+    let mut line_buffer = LineBuffer::new();
+    line_buffer.set_buffer("(a [b] \"(\" 'c)') }".to_string());
+
+    let brackets = line_buffer.brackets();
+    assert_eq!(brackets.pairs, vec![(3, 5), (0, 15)]);
+    assert_eq!(brackets.unmatched, vec![17]);
+
+    line_buffer.set_insertion_point(6);
+    assert_eq!(line_buffer.matching_brackets(&brackets), Some((3, 5)));
+    line_buffer.set_insertion_point(0);
+    assert_eq!(line_buffer.matching_brackets(&brackets), Some((0, 15)));
+    line_buffer.set_insertion_point(2);
+    assert_eq!(line_buffer.matching_brackets(&brackets), None);
+}
+
+#[test]
+fn clamped_insertion_point_test() {
+    let mut line_buffer = LineBuffer::new();
+    line_buffer.set_buffer("a😊b".to_string());
+
+    line_buffer.set_insertion_point_clamped(3);
+    assert_eq!(line_buffer.get_insertion_point(), 1);
+    line_buffer.set_insertion_point_clamped(5);
+    assert_eq!(line_buffer.get_insertion_point(), 5);
+    line_buffer.set_insertion_point_clamped(100);
+    assert_eq!(line_buffer.get_insertion_point(), 6);
+}
+
+#[test]
+fn code_point_and_shortcode_test() {
+    let mut line_buffer = LineBuffer::new();
+    line_buffer.insert_str(0, "hi U+1F60A");
+    line_buffer.move_to_end();
+    assert!(line_buffer.expand_code_point());
+    assert_eq!(&*line_buffer, "hi \u{1F60A}");
+    assert_eq!(line_buffer.get_insertion_point(), line_buffer.len());
+    line_buffer.insert_str(line_buffer.len(), " U+D800");
+    line_buffer.move_to_end();
+    assert!(!line_buffer.expand_code_point());
+
+    line_buffer.set_buffer("ok :heart:".to_string());
+    line_buffer.move_to_end();
+    assert!(line_buffer.expand_shortcode());
+    assert_eq!(&*line_buffer, "ok \u{2764}\u{FE0F}");
+    assert_eq!(line_buffer.graphemes(true).count(), 4);
+
+    line_buffer.set_buffer("a:x:".to_string());
+    line_buffer.move_to_end();
+    assert!(!line_buffer.expand_shortcode());
+
+    line_buffer.set_buffer(":thu".to_string());
+    line_buffer.move_to_end();
+    assert!(line_buffer.complete_shortcode(false));
+    assert_eq!(&*line_buffer, ":thumbs");
+    assert!(!line_buffer.complete_shortcode(false));
+    line_buffer.insert_str(line_buffer.len(), "u");
+    line_buffer.move_to_end();
+    assert!(line_buffer.complete_shortcode(false));
+    assert_eq!(&*line_buffer, "\u{1F44D}");
+
+    line_buffer.set_buffer(":THU".to_string());
+    line_buffer.move_to_end();
+    assert!(!line_buffer.complete_shortcode(false));
+    assert!(line_buffer.complete_shortcode(true));
+    assert_eq!(&*line_buffer, ":thumbs");
+    assert_eq!(line_buffer.get_insertion_point(), line_buffer.len());
+}
+
+#[test]
+fn truncate_to_width_test() {
+    let facepalm = "\u{1F926}\u{1F3FC}\u{200D}\u{2642}\u{FE0F}";
+    assert_eq!(display_width(facepalm), 2);
+    assert_eq!(display_width("日本語\t"), 8);
+    assert_eq!(truncate_to_width(&format!("{}!", facepalm), 2), facepalm);
+    assert_eq!(truncate_to_width("日本語.txt", 5), "日本");
+    assert_eq!(truncate_to_width("日本語.txt", 6), "日本語");
+    assert_eq!(truncate_to_width("a\tb", 2), "a");
+    assert_eq!(truncate_to_width("abc", 10), "abc");
+}
@@ -1,55 +1,221 @@
-use crossterm::{
-    terminal::{self},
-    Result,
+use crossterm::{style::Print, ExecutableCommand};
+use red_line::{
+    print_crlf, print_events, print_message, Engine, InputConstraints, Inputrc,
+    InvalidInputFeedback, PasswordMask, Result, Selection, Signal, SubmitDecision,
+};
+use std::{
+    io::stdout,
+    path::Path,
+    process::Command,
+    thread,
+    time::{Duration, Instant},
 };
-use std::io::stdout;
-mod line_buffer;
-
-mod engine;
-use engine::{print_crlf, print_message, Engine, Signal};
 
-mod diagnostic;
-use diagnostic::print_events;
+fn main() {
+    if let Err(err) = run() {
+        // The terminal is back to normal by now, so this can go to stderr as is
+        eprintln!("red_line: {}", err);
+        std::process::exit(1);
+    }
+}
 
-fn main() -> Result<()> {
+fn run() -> Result<()> {
     let mut stdout = stdout();
 
-    terminal::enable_raw_mode()?;
     // quick command like parameter handling
     let args: Vec<String> = std::env::args().collect();
     // if -k is passed, show the events
     if args.len() > 1 && args[1] == "-k" {
         print_message(&mut stdout, "Ready to print events:")?;
         print_events(&mut stdout)?;
-        terminal::disable_raw_mode()?;
         println!();
         return Ok(());
     };
 
     let mut engine = Engine::new();
 
+    engine.add_submit_hook(|buffer| {
+        if buffer.ends_with(char::is_whitespace) {
+            SubmitDecision::Rewrite(buffer.trim_end().to_string())
+        } else {
+            SubmitDecision::Accept
+        }
+    });
+
+    engine.register_command("wrap-sudo", |line_buffer| {
+        if !line_buffer.starts_with("sudo ") {
+            line_buffer.insert_str(0, "sudo ");
+            line_buffer.set_insertion_point(line_buffer.get_insertion_point() + "sudo ".len());
+        }
+    });
+    engine.register_command("insert-git-branch", |line_buffer| {
+        let output = Command::new("git")
+            .args(["rev-parse", "--abbrev-ref", "HEAD"])
+            .output();
+        if let Ok(output) = output {
+            let branch = String::from_utf8_lossy(&output.stdout);
+            let branch = branch.trim();
+            line_buffer.insert_str(line_buffer.get_insertion_point(), branch);
+            line_buffer.set_insertion_point(line_buffer.get_insertion_point() + branch.len());
+        }
+    });
+
+    // pick up the user's readline bindings, if they have any
+    match Inputrc::load_default("red_line") {
+        Ok(Some(inputrc)) => {
+            for warning in &inputrc.warnings {
+                print_message(&mut stdout, warning)?;
+            }
+            engine.set_keybindings(inputrc.keybindings);
+            engine.set_completion_ignore_case(inputrc.completion_ignore_case);
+            if let Some(keyseq_timeout) = inputrc.keyseq_timeout {
+                engine.set_chord_timeout(keyseq_timeout);
+            }
+        }
+        Ok(None) => {}
+        Err(err) => print_message(&mut stdout, &err.to_string())?,
+    }
+
+    // and the shared settings of the project we're started in
+    let config_path = Path::new("red_line.toml");
+    if config_path.exists() {
+        if let Err(err) = engine.load_config(config_path) {
+            print_message(&mut stdout, &err.to_string())?;
+        }
+    }
+
     loop {
-        if let Ok(sig) = engine.read_line(&mut stdout) {
-            match sig {
-                Signal::CtrlD => {
+        let sig = match engine.read_line(&mut stdout) {
+            Ok(sig) => sig,
+            Err(err) if err.is_recoverable() => {
+                print_message(&mut stdout, &err.to_string())?;
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+        match sig {
+            Signal::CtrlD => {
+                break;
+            }
+            Signal::Success(buffer) => {
+                if (buffer.trim() == "exit") || (buffer.trim() == "logout") {
                     break;
                 }
-                Signal::Success(buffer) => {
-                    if (buffer.trim() == "exit") || (buffer.trim() == "logout") {
-                        break;
+                if let Some(name) = buffer.trim().strip_prefix("rename ") {
+                    print_crlf(&mut stdout)?;
+                    stdout.execute(Print("Rename to: "))?;
+                    if let Ok(Signal::Success(new_name)) =
+                        engine.read_line_with_initial(&mut stdout, name, name.len())
+                    {
+                        print_message(&mut stdout, &format!("{} -> {}", name, new_name))?;
+                    } else {
+                        print_crlf(&mut stdout)?;
+                    }
+                    continue;
+                }
+                if buffer.trim() == "password" {
+                    print_message(&mut stdout, "Password:")?;
+                    if let Ok(Signal::Success(password)) =
+                        engine.read_password(&mut stdout, PasswordMask::Char('*'))
+                    {
+                        print_message(
+                            &mut stdout,
+                            &format!("Got {} characters", password.chars().count()),
+                        )?;
+                    } else {
+                        print_crlf(&mut stdout)?;
+                    }
+                    continue;
+                }
+                if buffer.trim() == "quick" {
+                    print_message(&mut stdout, "You have 5 seconds:")?;
+                    let deadline = Instant::now() + Duration::from_secs(5);
+                    match engine.read_line_until(&mut stdout, Some(deadline), None) {
+                        Ok(Signal::Success(answer)) => {
+                            print_message(&mut stdout, &format!("In time: {}", answer))?;
+                        }
+                        Ok(Signal::Timeout(partial)) => {
+                            print_message(&mut stdout, &format!("Too slow, got {:?}", partial))?;
+                        }
+                        _ => print_crlf(&mut stdout)?,
+                    }
+                    continue;
+                }
+                if buffer.trim() == "jobs" {
+                    // Pretend to run some jobs that report back while we keep typing
+                    for i in 1..=3 {
+                        let printer = engine.create_external_printer();
+                        thread::spawn(move || {
+                            thread::sleep(Duration::from_secs(i));
+                            printer.print(format!("job {} done", i));
+                        });
+                    }
+                    print_crlf(&mut stdout)?;
+                    continue;
+                }
+                if buffer.trim() == "pick" || buffer.trim() == "pick -m" {
+                    let mut files: Vec<String> = std::fs::read_dir(".")?
+                        .filter_map(|entry| entry.ok())
+                        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                        .collect();
+                    files.sort();
+                    print_message(&mut stdout, "Pick a file:")?;
+                    let multi_select = buffer.trim() == "pick -m";
+                    if let Ok(Selection::Selected(picked)) =
+                        engine.read_selection(&mut stdout, &files, multi_select)
+                    {
+                        let picked: Vec<&str> =
+                            picked.iter().map(|&index| files[index].as_str()).collect();
+                        print_message(&mut stdout, &format!("Picked {}", picked.join(", ")))?;
+                    } else {
+                        print_crlf(&mut stdout)?;
                     }
-                    print_message(&mut stdout, &format!("Our buffer: {}", buffer))?;
+                    continue;
                 }
-                Signal::CtrlC => {
-                    // We need to move one line down to start with the prompt on a new line
+                if buffer.trim() == "confirm" {
+                    print_crlf(&mut stdout)?;
+                    if let Ok(Signal::Success(answer)) =
+                        engine.read_confirmation(&mut stdout, "Overwrite?", false)
+                    {
+                        print_message(&mut stdout, &format!("Answered {}", answer))?;
+                    } else {
+                        print_crlf(&mut stdout)?;
+                    }
+                    continue;
+                }
+                if buffer.trim() == "port" {
+                    print_message(&mut stdout, "Port:")?;
+                    let constraints = InputConstraints::new()
+                        .with_max_length(5)
+                        .with_char_filter(|c| c.is_ascii_digit())
+                        .with_pattern("^[1-9][0-9]*$")
+                        .expect("valid pattern")
+                        .with_feedback(InvalidInputFeedback::Flash);
+                    if let Ok(Signal::Success(port)) =
+                        engine.read_line_with_constraints(&mut stdout, &constraints)
+                    {
+                        print_message(&mut stdout, &format!("Listening on {}", port))?;
+                    } else {
+                        print_crlf(&mut stdout)?;
+                    }
+                    continue;
+                }
+                print_message(&mut stdout, &format!("Our buffer: {}", buffer))?;
+            }
+            Signal::CtrlC | Signal::Timeout(_) | Signal::Interrupted(_) => {
+                // We need to move one line down to start with the prompt on a new line
+                print_crlf(&mut stdout)?;
+            }
+            Signal::Host(name) => {
+                if name == "help" {
+                    print_message(&mut stdout, "Type a line and press enter, C-d to quit.")?;
+                } else {
                     print_crlf(&mut stdout)?;
                 }
             }
         }
     }
 
-    terminal::disable_raw_mode()?;
-
     println!();
     Ok(())
 }
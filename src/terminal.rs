@@ -0,0 +1,111 @@
+use std::{
+    io::{stdin, stdout, IsTerminal, Write},
+    panic,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Once,
+    },
+};
+
+use crossterm::{
+    cursor::Show,
+    style::{Print, ResetColor},
+    terminal, QueueableCommand,
+};
+
+use crate::error::{Error, Result};
+
+// How many guards are alive, the terminal is in raw mode while there are any
+static GUARDS: AtomicUsize = AtomicUsize::new(0);
+static PANIC_HOOK: Once = Once::new();
+
+// DECSCUSR 0, whatever cursor shape the terminal was set up with
+pub(crate) const DEFAULT_CURSOR_SHAPE: &str = "\x1b[0 q";
+
+/// Keeps the terminal in raw mode for as long as it lives. `read_line` and the
+/// other prompts take one themselves, so applications don't need to.
+///
+/// Guards nest, and raw mode ends when the last one is dropped, also while
+/// unwinding from a panic. The first guard installs a panic hook that restores
+/// the terminal before the panic message is printed.
+pub struct RawModeGuard {
+    _private: (),
+}
+
+impl RawModeGuard {
+    /// Fails with `Error::NotATty` when stdin or stdout is redirected
+    pub fn new() -> Result<RawModeGuard> {
+        if !stdin().is_terminal() || !stdout().is_terminal() {
+            return Err(Error::NotATty);
+        }
+        install_panic_hook();
+
+        if GUARDS.fetch_add(1, Ordering::SeqCst) == 0 {
+            if let Err(err) = terminal::enable_raw_mode() {
+                GUARDS.fetch_sub(1, Ordering::SeqCst);
+                return Err(err.into());
+            }
+        }
+
+        Ok(RawModeGuard { _private: () })
+    }
+}
+
+impl Drop for RawModeGuard {
+    fn drop(&mut self) {
+        if GUARDS.fetch_sub(1, Ordering::SeqCst) == 1 {
+            // Nothing to be done about it failing while dropping
+            let _ = terminal::disable_raw_mode();
+        }
+    }
+}
+
+/// Stop the process the way Ctrl-Z does outside of raw mode, and return once
+/// it is continued with the terminal back in raw mode
+#[cfg(unix)]
+pub(crate) fn suspend() -> Result<()> {
+    // Hand the shell the cursor in its own shape, on a line of its own
+    let mut stdout = stdout();
+    stdout
+        .queue(Print(DEFAULT_CURSOR_SHAPE))?
+        .queue(Print("\r\n"))?;
+    stdout.flush()?;
+    terminal::disable_raw_mode()?;
+    // The default action of SIGTSTP stops us right here, until SIGCONT
+    unsafe {
+        libc::raise(libc::SIGTSTP);
+    }
+    terminal::enable_raw_mode()?;
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn suspend() -> Result<()> {
+    Ok(())
+}
+
+fn install_panic_hook() {
+    PANIC_HOOK.call_once(|| {
+        let previous_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            // Otherwise the message comes out as a staircase, in whatever color
+            // the prompt was being drawn in
+            if GUARDS.load(Ordering::SeqCst) > 0 {
+                restore_terminal();
+            }
+            previous_hook(info);
+        }));
+    });
+}
+
+fn restore_terminal() {
+    let _ = terminal::disable_raw_mode();
+
+    let mut stdout = stdout();
+    let _ = stdout
+        .queue(ResetColor)
+        .and_then(|stdout| stdout.queue(Show))
+        .and_then(|stdout| stdout.queue(Print("\n")));
+    let _ = stdout.flush();
+}